@@ -1,28 +1,46 @@
 use log::{debug, error, info};
 use std::{
     fmt::Debug,
-    process::Command,
+    process::{Command, ExitCode},
     sync::{mpsc, Arc},
     thread,
+    time::Duration,
 };
 
 pub mod runtime;
+mod watchdog;
+
+pub use watchdog::Watchdog;
+
+/// why [`Appflow::init`] stop waiting
+enum Stop {
+    Done,
+    Failed,
+    Hung,
+}
 
 /// application flow to hanlde application lifecycle
 /// Using std instead of tokio
 pub trait Appflow: Sync + Send + Sized + 'static {
+    /// error returned by [`Appflow::main_process`], e.g. the app own error enum
+    type Error: std::error::Error + Send + Sync + 'static;
+
     /// clean up process, default to doesnt do anything
     fn cleanup(&self) {}
+    /// process spawned by [`Appflow::restart`], default to current exe with the original args
+    /// override to drop one shot flag, add recovery flag or exec a wrapper
+    fn restart_command(&self) -> Command {
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command.args(std::env::args().skip(1)); // Pass arguments
+        command
+    }
     /// restart application
     fn restart(&self) {
         info!("Restarting application...");
         info!("Cleaning Up process");
         self.cleanup();
 
-        let current_exe = std::env::current_exe().unwrap();
-        let args = std::env::args().skip(1); // Pass arguments
-
-        if let Err(e) = Command::new(current_exe).args(args).spawn() {
+        if let Err(e) = self.restart_command().spawn() {
             error!("Failed to restart the program: {}", e);
         }
 
@@ -30,42 +48,134 @@ pub trait Appflow: Sync + Send + Sized + 'static {
         std::process::exit(0);
     }
     /// use this to be main wheel, the one that lives forever
-    /// therror will be logged and caught automatically
-    fn main_process(&self) -> Result<(), Box<dyn std::error::Error>>;
+    /// the error is logged and [`Appflow::init`] cleanup then return failure code
+    /// call [`Watchdog::pet`] every loop when [`Appflow::watchdog_timeout`] is set
+    fn main_process(&self, watchdog: &Watchdog) -> Result<(), Self::Error>;
+
+    /// main process not petting the watchdog this long is considered wedged
+    /// and the app is restarted with [`Appflow::restart`], default to disabled
+    fn watchdog_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// code returned by [`Appflow::init`] once its done, default to success
+    /// e.g. `to_exit_code(runtime.wait_for_exit())` so CI can see supervised job failed
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::SUCCESS
+    }
 
     /// Initialize the application
-    fn init(self) {
+    /// return [`Appflow::exit_code`] to be returned from `main`, or failure when main process failed
+    fn init(self) -> ExitCode {
         debug!("Initializing application...");
+        run(self, |app, watchdog| match run_once(app, watchdog) {
+            Ok(()) => Stop::Done,
+            Err(e) => {
+                error!("Main process failed: {e}");
+                Stop::Failed
+            }
+        })
+    }
+
+    /// same as [`Appflow::init`] but rerun `main_process` when it return error
+    /// wait `backoff` before first retry and double it every next one,
+    /// give up and cleanup after `max_retries` consecutive failure, returning failure code
+    fn init_with_restart(self, max_retries: u32, backoff: Duration) -> ExitCode {
+        debug!("Initializing application with restart...");
+        run(self, move |app, watchdog| {
+            let mut retries = 0;
+            let mut delay = backoff;
+            loop {
+                match run_once(app, watchdog) {
+                    Ok(_) => break Stop::Done,
+                    Err(e) if retries >= max_retries => {
+                        error!("Main process failed: {e}, giving up after {retries} retries");
+                        break Stop::Failed;
+                    }
+                    Err(e) => {
+                        retries += 1;
+                        error!(
+                            "Main process failed: {e}, retry {retries}/{max_retries} in {delay:?}"
+                        );
+                        thread::sleep(delay);
+                        delay = delay.saturating_mul(2);
+                    }
+                }
+            }
+        })
+    }
+}
 
-        let (tx, rx) = mpsc::channel();
+/// run `main_loop` on its own thread until it, ctrl-c or the watchdog stop the app
+/// shared by [`Appflow::init`] and [`Appflow::init_with_restart`]
+fn run<A: Appflow>(
+    app: A,
+    main_loop: impl FnOnce(&A, &Watchdog) -> Stop + Send + 'static,
+) -> ExitCode {
+    let (tx, rx) = mpsc::channel();
 
-        let m = Arc::new(self);
-        let m_tx = tx.clone();
-        let m_clone = m.clone();
+    let m = Arc::new(app);
+    let m_tx = tx.clone();
+    let m_clone = m.clone();
+    let watchdog = watch(m.as_ref(), &tx);
 
-        thread::spawn(move || {
-            info!("Starting main process...");
-            m_clone
-                .main_process()
-                .expect("Failed to start the main process");
-            let _ = m_tx.send(0);
-        });
+    thread::spawn(move || {
+        let stop = main_loop(&m_clone, &watchdog);
+        let _ = m_tx.send(stop);
+    });
 
-        ctrlc::set_handler(move || {
-            info!("SIGINT received, shutting down...");
-            let _ = tx.send(0);
-        })
-        .ok();
-
-        // witing either process to exit
-        match rx.recv() {
-            Ok(_) => {
-                info!("Attemp to shutdown gracefully.....");
-                m.cleanup();
-                info!("Application has been shutdown");
+    ctrlc::set_handler(move || {
+        info!("SIGINT received, shutting down...");
+        let _ = tx.send(Stop::Done);
+    })
+    .ok();
+
+    // witing either process to exit
+    match rx.recv() {
+        Ok(stop) => {
+            if let Some(code) = shutdown(m.as_ref(), stop) {
+                return code;
             }
-            Err(e) => error!("{:?}", e),
         }
+        Err(e) => error!("{:?}", e),
+    }
+    m.exit_code()
+}
+
+/// single run of the main process with the watchdog armed only while its running
+fn run_once<A: Appflow>(app: &A, watchdog: &Watchdog) -> Result<(), A::Error> {
+    info!("Starting main process...");
+    watchdog.pet();
+    let result = app.main_process(watchdog);
+    // backoff sleep isnt a hang
+    watchdog.disarm();
+    result
+}
+
+/// arm the watchdog to send [`Stop::Hung`] if its enabled
+fn watch<A: Appflow>(app: &A, tx: &mpsc::Sender<Stop>) -> Watchdog {
+    let watchdog = Watchdog::default();
+    if let Some(timeout) = app.watchdog_timeout() {
+        let tx = tx.clone();
+        watchdog.monitor(timeout, move || {
+            let _ = tx.send(Stop::Hung);
+        });
+    }
+    watchdog
+}
+
+/// cleanup or restart once [`Appflow::init`] stop waiting, return failure code if needed
+fn shutdown<A: Appflow>(app: &A, stop: Stop) -> Option<ExitCode> {
+    if let Stop::Hung = stop {
+        error!("Main process missed the watchdog deadline");
+        app.restart();
+    }
+    info!("Attemp to shutdown gracefully.....");
+    app.cleanup();
+    info!("Application has been shutdown");
+    match stop {
+        Stop::Failed => Some(ExitCode::FAILURE),
+        _ => None,
     }
 }
 
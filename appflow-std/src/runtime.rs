@@ -2,11 +2,49 @@ use super::AppResult;
 use indexmap::IndexMap;
 use log::{debug, error};
 use std::{
-    process::{Child, Command},
-    sync::{Arc, RwLock},
+    collections::BTreeMap,
+    future::Future,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus},
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
+/// shell style exit code of the status, `128 + signal` when killed by signal
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+/// aggregate exit code of supervised processes, the first nonzero one or 0 if all succeed
+pub fn aggregate_exit_code<'a>(statuses: impl IntoIterator<Item = &'a ExitStatus>) -> i32 {
+    statuses
+        .into_iter()
+        .map(exit_code)
+        .find(|code| *code != 0)
+        .unwrap_or(0)
+}
+
+/// exit code to return from `main` or [`Appflow::exit_code`](crate::Appflow::exit_code)
+/// code outside 1..=255 become 1 so failure is never reported as success
+pub fn to_exit_code(code: i32) -> std::process::ExitCode {
+    match code {
+        0 => std::process::ExitCode::SUCCESS,
+        code => std::process::ExitCode::from(u8::try_from(code).unwrap_or(1)),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum ProcessStatus {
     Running,
@@ -31,6 +69,10 @@ pub struct AppProcess {
     pub process: Option<Child>,
     pub status: ProcessStatus,
     pub args: Vec<String>,
+    /// extra environment variable on top of the inherited one
+    pub env: BTreeMap<String, String>,
+    /// working directory, default to the runtime one
+    pub cwd: Option<PathBuf>,
 }
 
 #[derive(Error, Debug)]
@@ -39,10 +81,32 @@ pub enum AppError {
     NotFound(String),
     #[error("Failed to execute command : {0}")]
     SubProcess(#[from] std::io::Error),
+    #[error("Update command of process {0} timed out")]
+    Timeout(String),
 }
 
 pub type AppRuntimeResult<T> = Result<T, AppError>;
 
+/// Operation shared by this runtime and the tokio `appruntime::AppRuntime`
+/// so orchestration code can be generic over both
+/// std implementation does the work before returning the future,
+/// so its fine to poll it once or block on it with any executor
+pub trait ProcessManager: Send + Sync {
+    type Process: Send;
+    type Status: Send;
+    type Error: std::error::Error + Send;
+
+    fn add(&self, app: Self::Process) -> impl Future<Output = ()> + Send;
+    fn start_all(&self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    fn stop(&self, id: &str) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    fn restart(&self, id: &str) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    fn check_status(
+        &self,
+        id: &str,
+    ) -> impl Future<Output = Result<Self::Status, Self::Error>> + Send;
+    fn list_status(&self) -> impl Future<Output = Vec<(String, Self::Status)>> + Send;
+}
+
 impl AppProcess {
     pub fn new(id: impl ToString, command: impl ToString, args: Vec<String>) -> Self {
         Self {
@@ -52,6 +116,27 @@ impl AppProcess {
             ..Default::default()
         }
     }
+
+    pub fn env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// e.g. separate data directory to run the same binary as isolated instance
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// command with the args, env and cwd of the process
+    fn to_command(&self) -> Command {
+        let mut command = Command::new(&self.command);
+        command.args(&self.args).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
 }
 
 /// To start runtime application to handle multiple process
@@ -74,23 +159,79 @@ impl AppProcess {
 ///```
 pub struct AppRuntime {
     pub apps: Arc<RwLock<IndexMap<String, AppProcess>>>,
+    /// how long the `--update` command may run before its killed
+    pub update_timeout: Duration,
 }
 
 impl Default for AppRuntime {
     fn default() -> Self {
         Self {
             apps: Arc::new(RwLock::new(IndexMap::new())),
+            update_timeout: Duration::from_secs(60),
         }
     }
 }
 
 impl AppRuntime {
+    /// Default: 60s
+    pub fn set_update_timeout(mut self, timeout: Duration) -> Self {
+        self.update_timeout = timeout;
+        self
+    }
+
+    /// the map is never left half updated, so the lock poisoned by panicking thread is still usable
+    /// recover it instead of cascading the panic into every next call
+    fn read(&self) -> RwLockReadGuard<'_, IndexMap<String, AppProcess>> {
+        self.apps.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, IndexMap<String, AppProcess>> {
+        self.apps.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// wait the child until it exit, kill it when exceed the timeout
+    fn wait_timeout(child: &mut Child, timeout: Duration, id: &str) -> AppRuntimeResult<()> {
+        let deadline = Instant::now() + timeout;
+        while child.try_wait()?.is_none() {
+            if Instant::now() >= deadline {
+                error!("Update command of Process {id} timed out, killing it");
+                child.kill().log()?;
+                child.wait().ok();
+                return Err(AppError::Timeout(id.to_string()));
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    }
+
+    /// run the process with update flag bounded by timeout, then respawn it
+    fn ver_update(app: &mut AppProcess, id: &str, timeout: Duration) -> AppRuntimeResult<()> {
+        debug!("Updating Process {id}");
+        if app.status == ProcessStatus::Running {
+            if let Some(process) = &mut app.process {
+                process.kill().log()?;
+            }
+        }
+        // run process with update flag
+        let mut update = app.to_command().arg("--update").spawn().log()?;
+        if let Err(e) = Self::wait_timeout(&mut update, timeout, id) {
+            app.status = ProcessStatus::Stopped;
+            return Err(e);
+        }
+
+        let child = app.to_command().spawn().log()?;
+        app.process = Some(child);
+        app.status = ProcessStatus::Running;
+        debug!("Succesfully Updating Process {id}");
+        Ok(())
+    }
+
     pub fn add_process(&self, app: AppProcess) {
         debug!("Adding Process {}", app.id);
 
         let id = app.id.clone();
 
-        let mut process = self.apps.write().unwrap();
+        let mut process = self.write();
         process.insert(app.id.clone(), app);
         debug!("Added Process {id} to runtime");
     }
@@ -98,15 +239,12 @@ impl AppRuntime {
         debug!("Adding Process {}", app.id);
 
         let id = app.id.clone();
-        let child = Command::new(app.command.clone())
-            .args(app.args.clone())
-            .spawn()
-            .log()?;
+        let child = app.to_command().spawn().log()?;
         debug!("Starting Process {id}");
         app.process = Some(child);
         app.status = ProcessStatus::Running;
 
-        let mut process = self.apps.write().unwrap();
+        let mut process = self.write();
         process.insert(app.id.clone(), app);
         Ok(())
     }
@@ -117,13 +255,10 @@ impl AppRuntime {
     }
 
     pub fn start_all(&self) -> AppRuntimeResult<()> {
-        let mut apps = self.apps.write().unwrap();
+        let mut apps = self.write();
         for (id, app) in apps.iter_mut() {
             debug!("Starting Process {id}");
-            let child = Command::new(app.command.clone())
-                .args(app.args.clone())
-                .spawn()
-                .log()?;
+            let child = app.to_command().spawn().log()?;
             app.process = Some(child);
             app.status = ProcessStatus::Running;
         }
@@ -133,17 +268,14 @@ impl AppRuntime {
     pub fn restart_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
         let id = id.as_ref();
         debug!("Restarting Process {id}");
-        let mut apps = self.apps.write().unwrap();
+        let mut apps = self.write();
         if let Some(app) = apps.get_mut(id) {
             if app.status == ProcessStatus::Running {
                 if let Some(process) = &mut app.process {
                     process.kill().log()?;
                 }
             }
-            let child = Command::new(app.command.clone())
-                .args(app.args.clone())
-                .spawn()
-                .log()?;
+            let child = app.to_command().spawn().log()?;
             app.process = Some(child);
             app.status = ProcessStatus::Running;
             debug!("Succesfully Restarting Process {id}");
@@ -156,7 +288,7 @@ impl AppRuntime {
 
     /// Using indexmap so the process start in order
     pub fn restart_all(&self) -> AppRuntimeResult<()> {
-        let mut apps = self.apps.write().unwrap();
+        let mut apps = self.write();
         for (id, app) in apps.iter_mut() {
             debug!("Restarting Process {id}");
             if app.status == ProcessStatus::Running {
@@ -164,10 +296,7 @@ impl AppRuntime {
                     process.kill().log()?;
                 }
             }
-            let child = Command::new(app.command.clone())
-                .args(app.args.clone())
-                .spawn()
-                .log()?;
+            let child = app.to_command().spawn().log()?;
             app.process = Some(child);
             app.status = ProcessStatus::Running;
             debug!("Succesfully Restarting Process {id}");
@@ -175,9 +304,48 @@ impl AppRuntime {
         Ok(())
     }
 
+    pub fn version_update_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        let mut apps = self.write();
+        if let Some(app) = apps.get_mut(id) {
+            Self::ver_update(app, id, self.update_timeout)
+        } else {
+            error!("Process {id} not found");
+            Err(AppError::NotFound(id.to_string()))
+        }
+    }
+
+    /// Using indexmap so the process start in order
+    pub fn version_update_all(&self) -> AppRuntimeResult<()> {
+        let mut apps = self.write();
+        for (id, app) in apps.iter_mut() {
+            Self::ver_update(app, id, self.update_timeout)?;
+        }
+        Ok(())
+    }
+
+    /// remove the process from the runtime leaving it running, e.g. hand it off to init
+    /// return its pid, None when it wasnt running
+    pub fn detach_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<Option<u32>> {
+        let id = id.as_ref();
+        let mut apps = self.write();
+        let Some(mut app) = apps.shift_remove(id) else {
+            error!("Process {id} not found");
+            return Err(AppError::NotFound(id.to_string()));
+        };
+        // dropping std child doesnt kill it
+        let pid = app
+            .process
+            .take()
+            .filter(|_| app.status == ProcessStatus::Running)
+            .map(|child| child.id());
+        debug!("Detached Process {id} with pid {pid:?}");
+        Ok(pid)
+    }
+
     pub fn stop_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
         let id = id.as_ref();
-        let mut apps = self.apps.write().unwrap();
+        let mut apps = self.write();
         if let Some(app) = apps.get_mut(id) {
             if app.status == ProcessStatus::Running {
                 if let Some(process) = &mut app.process {
@@ -194,7 +362,7 @@ impl AppRuntime {
     }
 
     pub fn stop_all(&self) -> AppRuntimeResult<()> {
-        let mut apps = self.apps.write().unwrap();
+        let mut apps = self.write();
         for (id, app) in apps.iter_mut() {
             if app.status == ProcessStatus::Running {
                 if let Some(process) = &mut app.process {
@@ -209,7 +377,7 @@ impl AppRuntime {
 
     pub fn check_status(&self, id: impl AsRef<str>) -> AppRuntimeResult<ProcessStatus> {
         let id = id.as_ref();
-        let apps = self.apps.read().unwrap();
+        let apps = self.read();
         if let Some(app) = apps.get(id) {
             return Ok(app.status.clone());
         }
@@ -220,7 +388,7 @@ impl AppRuntime {
     /// List id and status
     pub fn list_status(&self) -> Vec<(String, ProcessStatus)> {
         let mut con = vec![];
-        let apps = self.apps.read().unwrap();
+        let apps = self.read();
         for (id, process) in apps.iter() {
             con.push((id.clone(), process.status.clone()));
         }
@@ -228,7 +396,7 @@ impl AppRuntime {
     }
 
     pub fn update_status(&self) {
-        let mut apps = self.apps.write().unwrap();
+        let mut apps = self.write();
         for app in apps.values_mut() {
             if let Some(process) = &mut app.process {
                 if let Ok(status) = process.try_wait() {
@@ -242,14 +410,82 @@ impl AppRuntime {
         }
     }
 
-    pub fn wait_for_exit(&self) {
-        let mut apps = self.apps.write().unwrap();
+    /// block until every process exit, waiting them in order
+    /// return the [`aggregate_exit_code`] so the supervisor can exit with it
+    pub fn wait_for_exit(&self) -> i32 {
+        let mut apps = self.write();
+        let mut statuses = vec![];
         for app in apps.values_mut() {
             if let Some(process) = &mut app.process {
-                process.wait().unwrap();
+                statuses.extend(process.wait().log().ok());
             }
             app.status = ProcessStatus::Stopped;
         }
+        aggregate_exit_code(&statuses)
+    }
+
+    /// same as [`AppRuntime::wait_for_exit`] but poll every `interval`
+    /// and call `on_exit` with id and exit status as soon as each process exit
+    /// e.g. to update UI progressively
+    pub fn wait_for_exit_with(
+        &self,
+        interval: Duration,
+        mut on_exit: impl FnMut(&str, ExitStatus),
+    ) -> i32 {
+        let mut apps = self.write();
+        let mut statuses = vec![];
+        let mut running: Vec<_> = apps.values_mut().collect();
+        while !running.is_empty() {
+            running.retain_mut(|app| {
+                let exited = match &mut app.process {
+                    Some(process) => match process.try_wait().log() {
+                        Ok(None) => return true,
+                        Ok(Some(status)) => Some(status),
+                        Err(_) => None,
+                    },
+                    None => None,
+                };
+                app.status = ProcessStatus::Stopped;
+                if let Some(status) = exited {
+                    on_exit(&app.id, status);
+                    statuses.push(status);
+                }
+                false
+            });
+            if !running.is_empty() {
+                thread::sleep(interval);
+            }
+        }
+        aggregate_exit_code(&statuses)
+    }
+}
+
+impl ProcessManager for AppRuntime {
+    type Process = AppProcess;
+    type Status = ProcessStatus;
+    type Error = AppError;
+
+    fn add(&self, app: AppProcess) -> impl Future<Output = ()> + Send {
+        self.add_process(app);
+        std::future::ready(())
+    }
+    fn start_all(&self) -> impl Future<Output = AppRuntimeResult<()>> + Send {
+        std::future::ready(self.start_all())
+    }
+    fn stop(&self, id: &str) -> impl Future<Output = AppRuntimeResult<()>> + Send {
+        std::future::ready(self.stop_process(id))
+    }
+    fn restart(&self, id: &str) -> impl Future<Output = AppRuntimeResult<()>> + Send {
+        std::future::ready(self.restart_process(id))
+    }
+    fn check_status(
+        &self,
+        id: &str,
+    ) -> impl Future<Output = AppRuntimeResult<ProcessStatus>> + Send {
+        std::future::ready(self.check_status(id))
+    }
+    fn list_status(&self) -> impl Future<Output = Vec<(String, ProcessStatus)>> + Send {
+        std::future::ready(self.list_status())
     }
 }
 
@@ -300,7 +536,9 @@ mod testing {
     }
 
     impl Appflow for App {
-        fn main_process(&self) -> Result<(), Box<dyn std::error::Error>> {
+        type Error = AppError;
+
+        fn main_process(&self, _watchdog: &crate::Watchdog) -> Result<(), AppError> {
             self.start_all()?;
             std::thread::sleep(std::time::Duration::from_secs(2));
             self.restart_all()?;
@@ -324,4 +562,92 @@ mod testing {
         let app = App::default();
         app.init();
     }
+
+    /// std future is already complete, poll it once
+    fn ready<F: Future>(future: F) -> F::Output {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match std::pin::pin!(future).poll(&mut cx) {
+            std::task::Poll::Ready(output) => output,
+            std::task::Poll::Pending => panic!("std ProcessManager future is pending"),
+        }
+    }
+
+    /// written against the trait only, so it also run on tokio runtime
+    async fn cycle<M: ProcessManager>(manager: &M, app: M::Process, id: &str) -> Vec<String> {
+        manager.add(app).await;
+        manager.start_all().await.unwrap();
+        manager.check_status(id).await.unwrap();
+        manager.stop(id).await.unwrap();
+        manager
+            .list_status()
+            .await
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn generic_process_manager() {
+        let runtime = AppRuntime::default();
+        let app = AppProcess::new("sleep", "sh", vec!["-c".to_string(), "sleep 5".to_string()]);
+        assert_eq!(ready(cycle(&runtime, app, "sleep")), ["sleep"]);
+        assert_eq!(
+            runtime.check_status("sleep").unwrap(),
+            ProcessStatus::Stopped
+        );
+        assert!(ready(ProcessManager::restart(&runtime, "missing")).is_err());
+    }
+
+    #[test]
+    fn spawn_with_env_and_cwd() {
+        let runtime = AppRuntime::default();
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let script = format!("test \"$NAME\" = one && test \"$(pwd -P)\" = {dir:?}");
+        let app = AppProcess::new("env", "sh", vec!["-c".to_string(), script])
+            .env("NAME", "one")
+            .cwd(&dir);
+        runtime.add_process_then_run(app).unwrap();
+        assert_eq!(runtime.wait_for_exit(), 0);
+    }
+
+    #[test]
+    fn survive_poisoned_lock() {
+        let runtime = Arc::new(AppRuntime::default());
+        runtime.add_process(AppProcess::new("true", "true", vec![]));
+        let poisoner = runtime.clone();
+        let panicked = thread::spawn(move || {
+            let _apps = poisoner.apps.write().unwrap();
+            panic!("panic while holding the lock");
+        })
+        .join();
+        assert!(panicked.is_err() && runtime.apps.is_poisoned());
+
+        runtime.start_all().unwrap();
+        runtime.stop_all().unwrap();
+        assert_eq!(runtime.list_status().len(), 1);
+    }
+
+    #[test]
+    fn wait_for_exit_in_exit_order() {
+        let runtime = AppRuntime::default();
+        runtime.add_batch(vec![
+            AppProcess::new("slow", "sh", vec!["-c".to_string(), "sleep 1".to_string()]),
+            AppProcess::new("fast", "sh", vec!["-c".to_string(), "exit 3".to_string()]),
+        ]);
+        runtime.start_all().unwrap();
+
+        let mut exited = vec![];
+        let code = runtime.wait_for_exit_with(Duration::from_millis(20), |id, status| {
+            exited.push((id.to_string(), status.code()));
+        });
+        assert_eq!(code, 3);
+        assert_eq!(
+            exited,
+            [("fast".to_string(), Some(3)), ("slow".to_string(), Some(0))]
+        );
+        assert!(runtime
+            .list_status()
+            .iter()
+            .all(|(_, status)| *status == ProcessStatus::Stopped));
+    }
 }
@@ -0,0 +1,67 @@
+use log::error;
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// handle given to [`Appflow::main_process`](crate::Appflow::main_process) to prove its main loop
+/// isnt wedged, only watched when [`Appflow::watchdog_timeout`](crate::Appflow::watchdog_timeout) is set
+#[derive(Debug, Clone, Default)]
+pub struct Watchdog {
+    /// None when its not armed, e.g. between retries
+    last_pet: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Watchdog {
+    /// tell the watchdog the main loop is still alive, call it more often than the timeout
+    pub fn pet(&self) {
+        *self.last_pet.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub(crate) fn disarm(&self) {
+        *self.last_pet.lock().unwrap() = None;
+    }
+
+    /// call `on_hang` on background thread once its not pet within `timeout` while armed
+    /// the thread exit after that or once every handle is dropped
+    pub(crate) fn monitor(&self, timeout: Duration, on_hang: impl FnOnce() + Send + 'static) {
+        let last_pet = Arc::downgrade(&self.last_pet);
+        let tick = (timeout / 4).max(Duration::from_millis(10));
+        thread::spawn(move || loop {
+            thread::sleep(tick);
+            let Some(last_pet) = last_pet.upgrade() else {
+                return;
+            };
+            let since = last_pet.lock().unwrap().map(|t| t.elapsed());
+            if let Some(since) = since.filter(|since| *since > timeout) {
+                error!("Watchdog not pet for {since:?}, main process is wedged");
+                on_hang();
+                return;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn fire_only_when_not_pet() {
+        let (tx, rx) = mpsc::channel();
+        let watchdog = Watchdog::default();
+        watchdog.monitor(Duration::from_millis(100), move || tx.send(()).unwrap());
+
+        // not armed yet
+        thread::sleep(Duration::from_millis(200));
+        for _ in 0..5 {
+            watchdog.pet();
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(rx.try_recv().is_err());
+
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+}
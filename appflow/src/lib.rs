@@ -1,38 +1,107 @@
 #![allow(async_fn_in_trait)]
 use log::{debug, error, info, warn};
-use std::{fmt::Debug, process::Command, sync::Arc};
+use std::{
+    fmt::Debug,
+    process::{Command, ExitCode},
+    sync::Arc,
+};
 use tokio::signal;
 
+/// tokio process runtime, the same as the `appruntime` crate which is the canonical one
+/// so it has parity with `appflow_std::runtime`
+#[cfg(feature = "runtime")]
+pub use appruntime as runtime;
+
+#[cfg(feature = "runtime")]
+mod supervisor;
+#[cfg(feature = "runtime")]
+pub use supervisor::{run_supervisor, SupervisorConfig};
+
 #[cfg(feature = "update")]
 mod upp {
     pub use reqwest::{
-        header::{HeaderMap, HeaderValue, ACCEPT},
-        StatusCode,
+        header::{
+            HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_RANGES, AUTHORIZATION,
+            CONTENT_RANGE, ETAG, IF_RANGE, RANGE, USER_AGENT,
+        },
+        Certificate, StatusCode,
+    };
+    pub use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    pub use std::{
+        collections::hash_map::RandomState,
+        fs,
+        hash::{BuildHasher, Hasher},
+        io::Write,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        time::Duration,
     };
-    pub use serde::{Deserialize, Serialize};
-    pub use std::{fs, os::unix::fs::PermissionsExt};
     pub use thiserror::Error;
+    pub use tokio::task::JoinHandle;
 }
 #[cfg(feature = "update")]
 use upp::*;
 
 #[cfg(feature = "update")]
+#[derive(Clone, Debug, Default)]
 pub struct GithubUpdater {
     pub repo: String,
     pub owner: String,
     pub token: Option<String>,
     pub app_name: String,
+    /// version of the running binary, compared against the latest release name
+    pub current_version: Option<String>,
+    /// run the downloaded binary before replacing the current one
+    pub validate_new_binary: bool,
+    /// argument used to validate the new binary, default to `--version`
+    pub check_arg: Option<String>,
+    /// default to `Rust-Updater`
+    pub user_agent: Option<String>,
+    /// merged into both release info and asset download request
+    pub extra_headers: HeaderMap,
+    /// pin update to specific release tag instead of the latest
+    pub tag: Option<String>,
+    /// consider prerelease as the latest release, e.g. beta channel
+    pub include_prereleases: bool,
+    /// log download progress on info level every 10%
+    pub log_progress: bool,
+    /// extra trusted root certificate, e.g. github enterprise private ca
+    pub root_certificates: Vec<Certificate>,
+    /// trust only [`GithubUpdater::root_certificates`], ignoring the system roots
+    pub pin_certificates: bool,
+    /// only consider asset of this content type, e.g. `application/octet-stream`
+    /// so checksum or signature file is never picked
+    pub asset_content_type: Option<String>,
+    /// when no asset is named exactly `app_name`, accept one whose name contain
+    /// `app_name` with the current os and arch, e.g. `myapp-linux-x86_64`
+    pub match_platform: bool,
+    /// used for every request when set, e.g. configured with proxy or timeout
+    /// otherwise see [`GithubUpdater::client`]
+    pub http_client: Option<reqwest::Client>,
+    /// built on first request, shared by clone of the updater
+    default_client: Arc<std::sync::OnceLock<reqwest::Client>>,
 }
 
+/// connect timeout of the default client, there is no overall timeout
+/// since downloading large asset on slow link can take long
+#[cfg(feature = "update")]
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[cfg(feature = "update")]
 #[derive(Serialize, Deserialize)]
 pub struct ApiResponse {
     pub name: String,
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
     pub assets: Vec<ApiResponseAsset>,
 }
 
 #[cfg(feature = "update")]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ApiResponseAsset {
     pub name: String,
     pub size: u32,
@@ -40,6 +109,45 @@ pub struct ApiResponseAsset {
     pub url: String,
 }
 
+#[cfg(feature = "update")]
+impl ApiResponseAsset {
+    /// content type compared case insensitively, ignoring parameter like `; charset=`
+    pub fn has_content_type(&self, content_type: &str) -> bool {
+        let own = self.content_type.split(';').next().unwrap_or_default();
+        own.trim().eq_ignore_ascii_case(content_type.trim())
+    }
+
+    /// name contain `app_name`, [`std::env::consts::OS`] and [`std::env::consts::ARCH`]
+    /// `aarch64` also match `arm64` and `x86_64` match `amd64`
+    pub fn matches_platform(&self, app_name: &str) -> bool {
+        let name = self.name.to_lowercase();
+        let arch = match std::env::consts::ARCH {
+            "x86_64" => &["x86_64", "amd64"][..],
+            "aarch64" => &["aarch64", "arm64"][..],
+            arch => &[arch][..],
+        };
+        name.contains(&app_name.to_lowercase())
+            && name.contains(std::env::consts::OS)
+            && arch.iter().any(|a| name.contains(a))
+    }
+}
+
+/// what [`GithubUpdater::update`] would do, returned by [`GithubUpdater::update_dry_run`]
+#[cfg(feature = "update")]
+#[derive(Debug, Clone)]
+pub struct UpdatePlan {
+    /// name of the targeted release
+    pub release: String,
+    /// false when the release match the current version
+    pub update_available: bool,
+    pub asset: ApiResponseAsset,
+    /// executable that would be replaced, no backup of it is kept
+    pub target_path: PathBuf,
+    /// where the asset would be downloaded then renamed over `target_path`,
+    /// its resume info is kept next to it with `.meta` suffix until the download complete
+    pub download_path: PathBuf,
+}
+
 #[cfg(feature = "update")]
 #[derive(Debug, Error)]
 pub enum UpdateError {
@@ -53,40 +161,157 @@ pub enum UpdateError {
 
 #[cfg(feature = "update")]
 impl GithubUpdater {
-    pub async fn get_update_info(&self) -> Result<(ApiResponse, HeaderMap), UpdateError> {
-        let client = reqwest::Client::new();
+    pub fn new(owner: impl ToString, repo: impl ToString, app_name: impl ToString) -> Self {
+        Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            app_name: app_name.to_string(),
+            ..Default::default()
+        }
+    }
 
-        let url = format!(
-            "https://api.github.com/repos/{}/{}/releases/latest",
-            self.owner, self.repo
-        );
+    pub fn set_token(mut self, token: impl ToString) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
 
-        debug!("Fetching: {}", url);
+    /// set the running version, usually `env!("CARGO_PKG_VERSION")`
+    /// compared with the release tag, leading `v` ignored on both
+    pub fn set_current_version(mut self, version: impl ToString) -> Self {
+        self.current_version = Some(version.to_string());
+        self
+    }
+
+    /// validate the new binary by running it with `check_arg` before replacing
+    pub fn set_validate_new_binary(mut self, validate: bool, check_arg: Option<String>) -> Self {
+        self.validate_new_binary = validate;
+        self.check_arg = check_arg;
+        self
+    }
+
+    /// pin update into the release tag, useful to rollback to specific version
+    pub fn set_tag(mut self, tag: impl ToString) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn set_include_prereleases(mut self, include: bool) -> Self {
+        self.include_prereleases = include;
+        self
+    }
+
+    /// report download progress through the logger, e.g. for unattended update
+    pub fn set_log_progress(mut self, log_progress: bool) -> Self {
+        self.log_progress = log_progress;
+        self
+    }
+
+    /// pick only asset of the content type, see [`GithubUpdater::asset_content_type`]
+    pub fn set_asset_content_type(mut self, content_type: impl ToString) -> Self {
+        self.asset_content_type = Some(content_type.to_string());
+        self
+    }
+
+    /// fallback to asset named after the current platform, see [`GithubUpdater::match_platform`]
+    pub fn set_match_platform(mut self, match_platform: bool) -> Self {
+        self.match_platform = match_platform;
+        self
+    }
+
+    pub fn set_user_agent(mut self, user_agent: impl ToString) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// trust the certificate on top of the system roots
+    /// parse it with `Certificate::from_pem` or `Certificate::from_der`
+    pub fn add_root_certificate(mut self, cert: Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self.default_client = Default::default();
+        self
+    }
+
+    /// only trust the added root certificate so MITM cant feed malicious binary
+    /// even with cert signed by system trusted ca
+    pub fn set_pin_certificates(mut self, pin: bool) -> Self {
+        self.pin_certificates = pin;
+        self.default_client = Default::default();
+        self
+    }
+
+    /// use the client for every request so connection is reused and proxy/tls is configured
+    /// in one place, the certificate setting of the updater doesnt apply to it
+    pub fn set_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
 
-        let mut headers = reqwest::header::HeaderMap::new();
+    /// the injected client or the default one built once,
+    /// trusting the configured certificate with [`DEFAULT_CONNECT_TIMEOUT`]
+    fn client(&self) -> Result<reqwest::Client, UpdateError> {
+        if let Some(client) = &self.http_client {
+            return Ok(client.clone());
+        }
+        if let Some(client) = self.default_client.get() {
+            return Ok(client.clone());
+        }
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(DEFAULT_CONNECT_TIMEOUT)
+            .tls_built_in_root_certs(!self.pin_certificates);
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        let client = builder.build()?;
+        Ok(self.default_client.get_or_init(|| client).clone())
+    }
+
+    /// add header sent on every request, e.g. required by proxy
+    pub fn add_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.extra_headers.insert(name, value);
+        self
+    }
+
+    fn headers(&self) -> Result<HeaderMap, UpdateError> {
+        let mut headers = self.extra_headers.clone();
+        let user_agent = self.user_agent.as_deref().unwrap_or("Rust-Updater");
         headers.insert(
-            "User-Agent",
-            reqwest::header::HeaderValue::from_static("Rust-Updater"),
+            USER_AGENT,
+            HeaderValue::from_str(user_agent)
+                .map_err(|e| UpdateError::Custom(format!("Invalid user agent: {e}")))?,
         );
         if let Some(token) = &self.token {
             debug!("Using token: {}", token);
 
             headers.insert(
-                "Authorization",
-                reqwest::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}"))
+                    .map_err(|e| UpdateError::Custom(format!("Invalid token: {e}")))?,
             );
         }
+        Ok(headers)
+    }
+
+    /// fetch github api path of the repo, e.g. `releases/latest`
+    async fn fetch<T: DeserializeOwned>(&self, path: &str) -> Result<T, UpdateError> {
+        let client = self.client()?;
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/{path}",
+            self.owner, self.repo
+        );
+
+        debug!("Fetching: {}", url);
 
         let x = client
             .get(url)
-            .headers(headers.clone())
+            .headers(self.headers()?)
             .send()
             .await?
             .text()
             .await?;
 
-        match serde_json::from_str::<ApiResponse>(&x) {
-            Ok(x) => Ok((x, headers)),
+        match serde_json::from_str::<T>(&x) {
+            Ok(x) => Ok(x),
             Err(e) => {
                 log::error!(" the resulting data was: {x}");
                 Err(UpdateError::Custom(e.to_string()))
@@ -94,45 +319,230 @@ impl GithubUpdater {
         }
     }
 
+    /// release targeted by [`GithubUpdater::update`]
+    /// the pinned tag if set, otherwise the latest one following the prerelease channel
+    pub async fn get_update_info(&self) -> Result<(ApiResponse, HeaderMap), UpdateError> {
+        let release = match &self.tag {
+            Some(tag) => self.get_release_by_tag(tag).await?,
+            None if self.include_prereleases => self.get_latest_including_prereleases().await?,
+            None => self.fetch("releases/latest").await?,
+        };
+        Ok((release, self.headers()?))
+    }
+
+    pub async fn get_release_by_tag(
+        &self,
+        tag: impl AsRef<str>,
+    ) -> Result<ApiResponse, UpdateError> {
+        self.fetch(&format!("releases/tags/{}", tag.as_ref())).await
+    }
+
+    /// newest non draft release, `releases/latest` skip prerelease so walk the release list
+    pub async fn get_latest_including_prereleases(&self) -> Result<ApiResponse, UpdateError> {
+        for page in 1.. {
+            let releases: Vec<ApiResponse> = self
+                .fetch(&format!("releases?per_page=30&page={page}"))
+                .await?;
+            if releases.is_empty() {
+                break;
+            }
+            if let Some(release) = releases.into_iter().find(|r| !r.draft) {
+                return Ok(release);
+            }
+        }
+        Err(UpdateError::Custom("No release found".to_string()))
+    }
+
     pub async fn update(&self) -> Result<(), UpdateError> {
         let (update_info, header) = self.get_update_info().await?;
-        update_info.update_current_exe(&self.app_name, header).await
+        self.install(&update_info, header).await
+    }
+
+    /// check the latest release without downloading anything
+    /// return the release info if its differ from the current version
+    /// without current version set, the latest release is always considered new
+    pub async fn check_update(&self) -> Result<Option<ApiResponse>, UpdateError> {
+        let (update_info, _) = self.get_update_info().await?;
+        if self.is_current(&update_info) {
+            debug!("Already on the latest version {}", update_info.version());
+            return Ok(None);
+        }
+        Ok(Some(update_info))
     }
+
+    /// compared against the release tag, its name is free text like `Release 1.2.0`
+    fn is_current(&self, release: &ApiResponse) -> bool {
+        self.current_version.as_ref().is_some_and(|current| {
+            release.version().trim_start_matches('v') == current.trim_start_matches('v')
+        })
+    }
+
+    /// exact name first then the platform one, both filtered by the content type
+    fn select_asset<'a>(&self, release: &'a ApiResponse) -> Option<&'a ApiResponseAsset> {
+        let candidates = release.assets.iter().filter(|asset| {
+            self.asset_content_type
+                .as_ref()
+                .is_none_or(|content_type| asset.has_content_type(content_type))
+        });
+        candidates
+            .clone()
+            .find(|asset| asset.name == self.app_name)
+            .or_else(|| {
+                let mut candidates = candidates;
+                self.match_platform
+                    .then(|| candidates.find(|asset| asset.matches_platform(&self.app_name)))
+                    .flatten()
+            })
+    }
+
+    /// current executable and the temp path the download is written into
+    fn exe_paths() -> Result<(PathBuf, PathBuf), UpdateError> {
+        let current_exe = std::env::current_exe()?;
+        let temp_exe = current_exe.with_extension("temp");
+        Ok((current_exe, temp_exe))
+    }
+
+    /// resolve release, asset and paths like [`GithubUpdater::update`] without downloading
+    /// or touching the running binary, useful to validate configuration on CI/staging
+    pub async fn update_dry_run(&self) -> Result<UpdatePlan, UpdateError> {
+        let (release, _) = self.get_update_info().await?;
+        let asset = self
+            .select_asset(&release)
+            .cloned()
+            .ok_or_else(|| UpdateError::Custom("No asset found".to_string()))?;
+        let (target_path, download_path) = Self::exe_paths()?;
+        let plan = UpdatePlan {
+            update_available: !self.is_current(&release),
+            release: release.name,
+            asset,
+            target_path,
+            download_path,
+        };
+        info!("Update plan: {plan:?}");
+        Ok(plan)
+    }
+
+    /// check for update every `interval` plus random delay up to `jitter`
+    /// so fleet of binaries doesnt hit github at the same time
+    /// the callback decide whether to proceed with [`GithubUpdater::update`]
+    pub fn spawn_periodic_check<F>(
+        &self,
+        interval: Duration,
+        jitter: Duration,
+        on_available: F,
+    ) -> JoinHandle<()>
+    where
+        F: Fn(ApiResponse) + Send + 'static,
+    {
+        let updater = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval + random_jitter(jitter)).await;
+                match updater.check_update().await {
+                    Ok(Some(info)) => {
+                        info!("New version available: {}", info.name);
+                        on_available(info);
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to check for update: {e}"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "update")]
+fn random_jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return max;
+    }
+    // RandomState is seeded randomly, enough to spread the schedule without extra deps
+    let seed = RandomState::new().build_hasher().finish();
+    Duration::from_millis(seed % max.as_millis().max(1) as u64)
+}
+
+/// `path` with `.suffix` appended after its whole file name
+#[cfg(feature = "update")]
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// `<temp>.meta` holding the url, size and etag of the asset the partial download came from
+#[cfg(feature = "update")]
+fn partial_meta(temp: &Path) -> PathBuf {
+    with_suffix(temp, "meta")
+}
+
+/// etag to resume the partial download at `temp` with, None when its from other asset
+/// or the server gave no etag so it cant be checked
+#[cfg(feature = "update")]
+fn partial_etag(temp: &Path, asset: &ApiResponseAsset) -> Option<String> {
+    let meta = fs::read_to_string(partial_meta(temp)).ok()?;
+    let mut lines = meta.lines();
+    let same = lines.next() == Some(asset.url.as_str())
+        && lines.next() == Some(asset.size.to_string().as_str());
+    lines
+        .next()
+        .filter(|etag| same && !etag.is_empty())
+        .map(str::to_string)
+}
+
+/// remove the partial download and its meta
+#[cfg(feature = "update")]
+fn discard_partial(temp: &Path) {
+    fs::remove_file(temp).ok();
+    fs::remove_file(partial_meta(temp)).ok();
 }
 
 #[cfg(feature = "update")]
 impl ApiResponse {
+    /// canonical version of the release, its tag falling back to the name when it has none
+    pub fn version(&self) -> &str {
+        match self.tag_name.is_empty() {
+            true => &self.name,
+            false => &self.tag_name,
+        }
+    }
+
     pub async fn update_current_exe(
         &self,
         name_asset: impl ToString,
+        headers: HeaderMap,
+    ) -> Result<(), UpdateError> {
+        let updater = GithubUpdater {
+            app_name: name_asset.to_string(),
+            ..Default::default()
+        };
+        updater.install(self, headers).await
+    }
+}
+
+#[cfg(feature = "update")]
+impl GithubUpdater {
+    /// download the asset from the release and replace the current executable with it
+    pub async fn install(
+        &self,
+        release: &ApiResponse,
         mut headers: HeaderMap,
     ) -> Result<(), UpdateError> {
-        let client = reqwest::Client::new();
-        let x = self
-            .assets
-            .iter()
-            .find(|&y| y.name == name_asset.to_string());
-        if let Some(asset) = x {
+        let client = self.client()?;
+        if let Some(asset) = self.select_asset(release) {
             debug!("Found asset {}", asset.name);
             debug!("Downloading {}", asset.url);
 
             headers.insert(ACCEPT, HeaderValue::from_static("application/octet-stream"));
 
-            let res = client.get(&asset.url).headers(headers).send().await?;
-            if res.status() != StatusCode::OK {
-                return Err(UpdateError::Custom(format!("response status: {res:?}")));
-            }
-            let body = res.bytes().await?;
-
-            let body_str = std::str::from_utf8(&body).unwrap_or("<non-UTF-8 content>");
-            debug!("Downloaded body: {}", body_str);
-
-            let current_exe = std::env::current_exe().unwrap();
-            let temp_exe = current_exe.with_extension("temp");
+            let (current_exe, temp_exe) = Self::exe_paths()?;
 
             debug!("Writing to {}", temp_exe.display());
+            Self::download(&client, asset, headers, &temp_exe, self.log_progress).await?;
 
-            fs::write(&temp_exe, &body)?;
+            if self.validate_new_binary {
+                self.validate(&temp_exe).await?;
+            }
             // Replace the current executable with the new one
             //
             debug!("Replacing {}", current_exe.display());
@@ -156,6 +566,210 @@ impl ApiResponse {
         }
         Err(UpdateError::Custom("No asset found".to_string()))
     }
+
+    /// download several named asset of the release and place each one at its destination
+    /// e.g. the binary with its shared lib and default config
+    /// every asset is downloaded before any destination is touched,
+    /// if placing one of them fail the already placed one are rolled back
+    pub async fn update_assets(&self, mappings: Vec<(String, PathBuf)>) -> Result<(), UpdateError> {
+        let (release, mut headers) = self.get_update_info().await?;
+        headers.insert(ACCEPT, HeaderValue::from_static("application/octet-stream"));
+        let client = self.client()?;
+
+        let mut staged = vec![];
+        for (name, dest) in &mappings {
+            let asset = release
+                .assets
+                .iter()
+                .find(|a| &a.name == name)
+                .ok_or_else(|| UpdateError::Custom(format!("No asset found: {name}")))?;
+            staged.push((asset, dest, with_suffix(dest, "temp")));
+        }
+
+        for (asset, dest, temp) in &staged {
+            debug!("Downloading {} into {}", asset.name, temp.display());
+            let res = Self::download(&client, asset, headers.clone(), temp, self.log_progress);
+            if let Err(e) = res.await {
+                for (_, _, temp) in &staged {
+                    discard_partial(temp);
+                }
+                return Err(e);
+            }
+            // keep the mode of the replaced file, e.g. executable bit
+            if let Ok(meta) = fs::metadata(dest) {
+                fs::set_permissions(temp, meta.permissions())?;
+            }
+        }
+
+        let mut placed = vec![];
+        for (_, dest, temp) in &staged {
+            let backup = with_suffix(dest, "bak");
+            let had_old = dest.exists();
+            let res = if had_old {
+                fs::rename(dest, &backup).and_then(|_| fs::rename(temp, dest))
+            } else {
+                fs::rename(temp, dest)
+            };
+            if let Err(e) = res {
+                error!("Failed to place {}: {e}, rolling back", dest.display());
+                if had_old && !dest.exists() {
+                    fs::rename(&backup, dest).ok();
+                }
+                for (dest, backup, had_old) in placed.iter().rev() {
+                    if *had_old {
+                        fs::rename(backup, dest).ok();
+                    } else {
+                        fs::remove_file(dest).ok();
+                    }
+                }
+                for (_, _, temp) in &staged {
+                    discard_partial(temp);
+                }
+                return Err(e.into());
+            }
+            placed.push((*dest, backup, had_old));
+        }
+        for (_, backup, _) in placed {
+            fs::remove_file(backup).ok();
+        }
+        info!("Updated {} asset from {}", mappings.len(), release.name);
+        Ok(())
+    }
+
+    /// download the asset into `temp_exe`, resuming from the existing partial file
+    /// using range request, falling back to full download when the server doesnt support it.
+    /// partial is only resumed when its from the same asset and the server confirm with `If-Range`
+    /// its etag still match, otherwise its discarded so other release wont get appended to it
+    async fn download(
+        client: &reqwest::Client,
+        asset: &ApiResponseAsset,
+        headers: HeaderMap,
+        temp_exe: &Path,
+        log_progress: bool,
+    ) -> Result<(), UpdateError> {
+        let offset = fs::metadata(temp_exe).map(|m| m.len()).unwrap_or(0);
+        let etag = partial_etag(temp_exe, asset).filter(|_| offset > 0);
+        if etag.is_none() {
+            discard_partial(temp_exe);
+        }
+
+        let mut req = client.get(&asset.url).headers(headers.clone());
+        if let Some(etag) = &etag {
+            debug!("Resuming download from byte {offset}");
+            req = req
+                .header(RANGE, format!("bytes={offset}-"))
+                .header(IF_RANGE, etag);
+        }
+        let mut res = req.send().await?;
+
+        if res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // partial file is stale or bigger than the asset, start over
+            debug!("Range not satisfiable, restarting download");
+            discard_partial(temp_exe);
+            res = client.get(&asset.url).headers(headers).send().await?;
+        }
+
+        let resumed = etag.is_some()
+            && res.status() == StatusCode::PARTIAL_CONTENT
+            && res
+                .headers()
+                .get(ACCEPT_RANGES)
+                .is_none_or(|v| v.as_bytes() != b"none");
+        if !resumed && res.status() != StatusCode::OK {
+            return Err(UpdateError::Custom(format!("response status: {res:?}")));
+        }
+        let range_start = res
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with(&format!("bytes {offset}-")));
+        if resumed && !range_start {
+            discard_partial(temp_exe);
+            return Err(UpdateError::Custom(format!(
+                "Resumed download doesnt start at byte {offset}"
+            )));
+        }
+
+        let (mut file, start) = if resumed {
+            (fs::OpenOptions::new().append(true).open(temp_exe)?, offset)
+        } else {
+            let etag = res.headers().get(ETAG).and_then(|v| v.to_str().ok());
+            fs::write(
+                partial_meta(temp_exe),
+                format!(
+                    "{}\n{}\n{}",
+                    asset.url,
+                    asset.size,
+                    etag.unwrap_or_default()
+                ),
+            )?;
+            (fs::File::create(temp_exe)?, 0)
+        };
+        let expected = res
+            .content_length()
+            .map(|len| start + len)
+            .unwrap_or(asset.size as u64);
+
+        let mut received = start;
+        let mut logged = received * 10 / expected.max(1);
+        while let Some(chunk) = res.chunk().await? {
+            file.write_all(&chunk)?;
+            received += chunk.len() as u64;
+            let decile = received * 10 / expected.max(1);
+            if log_progress && decile > logged {
+                logged = decile;
+                info!(
+                    "Downloading {}: {}% ({received}/{expected} bytes)",
+                    asset.name,
+                    (decile * 10).min(100)
+                );
+            }
+        }
+        file.flush()?;
+
+        let written = fs::metadata(temp_exe)?.len();
+        if written != expected {
+            discard_partial(temp_exe);
+            return Err(UpdateError::Custom(format!(
+                "Downloaded size mismatch, expected {expected} got {written}"
+            )));
+        }
+        fs::remove_file(partial_meta(temp_exe)).ok();
+        debug!("Downloaded {written} bytes");
+        Ok(())
+    }
+
+    /// run the downloaded binary with the check argument and make sure it exit 0
+    /// the temp file is removed when the check failed so it wont be picked up later
+    async fn validate(&self, temp_exe: &Path) -> Result<(), UpdateError> {
+        #[cfg(unix)]
+        fs::set_permissions(temp_exe, fs::Permissions::from_mode(0o755))?;
+
+        let check_arg = self.check_arg.as_deref().unwrap_or("--version");
+        debug!("Validating {} with {check_arg}", temp_exe.display());
+
+        let status = match tokio::process::Command::new(temp_exe)
+            .arg(check_arg)
+            .status()
+            .await
+        {
+            Ok(status) => status,
+            Err(e) => {
+                fs::remove_file(temp_exe).ok();
+                return Err(UpdateError::Custom(format!(
+                    "Failed to run new binary: {e}"
+                )));
+            }
+        };
+        if !status.success() {
+            fs::remove_file(temp_exe).ok();
+            return Err(UpdateError::Custom(format!(
+                "New binary failed validation with {status}"
+            )));
+        }
+        debug!("New binary passed validation");
+        Ok(())
+    }
 }
 
 /// application flow to hanlde application lifecycle
@@ -171,25 +785,32 @@ pub trait Appflow: 'static + Sized {
         updater.update().await.unwrap();
     }
 
+    /// process spawned by [`Appflow::restart`], default to current exe with the original args
+    /// override to drop one shot flag, add recovery flag or exec a wrapper
+    fn restart_command(self: Arc<Self>) -> Command {
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command.args(std::env::args().skip(1));
+        command
+    }
+
     async fn restart(self: Arc<Self>) {
         info!("Restarting application...");
         info!("Cleaning Up process");
-        self.cleanup().await;
+        self.clone().cleanup().await;
 
-        let current_exe = std::env::current_exe().unwrap();
+        let mut command = self.restart_command();
 
         #[cfg(unix)]
         {
             use std::os::unix::process::CommandExt;
-            let _ = Command::new(&current_exe).exec();
+            let e = command.exec();
+            error!("Failed to restart the program: {e}, command : {command:?}");
         }
 
         #[cfg(windows)]
         {
-            let args = std::env::args().skip(1); // Pass arguments
-                                                 //
-            if let Err(e) = Command::new(&current_exe).args(args).spawn() {
-                error!("Failed to restart the program: {e}, path : {current_exe:?}");
+            if let Err(e) = command.spawn() {
+                error!("Failed to restart the program: {e}, command : {command:?}");
             }
 
             // Exit the current process
@@ -199,8 +820,15 @@ pub trait Appflow: 'static + Sized {
     /// use this to be main wheel, the one that lives forever
     async fn main_process(self: Arc<Self>);
 
+    /// code returned by [`Appflow::init`] once its done, default to success
+    /// e.g. `to_exit_code(runtime.wait_for_exit().await)` so CI can see supervised job failed
+    fn exit_code(self: Arc<Self>) -> ExitCode {
+        ExitCode::SUCCESS
+    }
+
     /// must be on tokio runtime
-    async fn init(self) {
+    /// return [`Appflow::exit_code`] to be returned from `main`
+    async fn init(self) -> ExitCode {
         debug!("Initializing application...");
 
         let s = Arc::new(self);
@@ -221,10 +849,11 @@ pub trait Appflow: 'static + Sized {
 
         tokio::select! {
             _ = signal::ctrl_c() => {
-                s_clone.cleanup().await;
+                s_clone.clone().cleanup().await;
             }
             _ = s.main_process() => {},
         }
+        s_clone.exit_code()
     }
 }
 
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use appruntime::{AppProcess, AppRuntime, AppRuntimeResult, RestartPolicy};
+use log::{error, info};
+use logger::Mylogger;
+
+/// settings of [`run_supervisor`]
+#[derive(Clone)]
+pub struct SupervisorConfig {
+    /// installed before anything else, None keep the logger already installed
+    pub logger: Option<Mylogger>,
+    /// how often exited process is checked against its restart policy
+    pub supervise_interval: Duration,
+    /// given to process without its own restart policy, None leave them stopped once exited
+    pub default_policy: Option<RestartPolicy>,
+}
+
+impl Default for SupervisorConfig {
+    /// default logger, checked every second, restarted on failure
+    fn default() -> Self {
+        Self {
+            logger: Some(Mylogger::default()),
+            supervise_interval: Duration::from_secs(1),
+            default_policy: Some(RestartPolicy::on_failure()),
+        }
+    }
+}
+
+impl SupervisorConfig {
+    pub fn logger(mut self, logger: Mylogger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// keep the logger installed by the caller
+    pub fn without_logger(mut self) -> Self {
+        self.logger = None;
+        self
+    }
+
+    pub fn supervise_interval(mut self, interval: Duration) -> Self {
+        self.supervise_interval = interval;
+        self
+    }
+
+    pub fn default_policy(mut self, policy: Option<RestartPolicy>) -> Self {
+        self.default_policy = policy;
+        self
+    }
+}
+
+/// wait for ctrl-c or SIGTERM on unix
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = term.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {e}");
+                tokio::signal::ctrl_c().await.ok();
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.ok();
+        "ctrl-c"
+    }
+}
+
+/// batteries included entry point for the common case, must be on tokio runtime
+/// init the logger, start every process then supervise them with restart until SIGINT/SIGTERM,
+/// after that every process is stopped and the log flushed.
+/// use [`AppRuntime`] directly for anything more custom
+/// ```ignore
+/// let web = AppProcess::new("web", "./server", vec![]);
+/// run_supervisor(vec![web], SupervisorConfig::default()).await?;
+/// ```
+pub async fn run_supervisor(
+    processes: Vec<AppProcess>,
+    config: SupervisorConfig,
+) -> AppRuntimeResult<()> {
+    if let Some(logger) = config.logger {
+        logger.init();
+    }
+    let runtime = AppRuntime::default();
+    for mut app in processes {
+        if app.restart_policy.is_none() {
+            app.restart_policy = config.default_policy.clone();
+        }
+        runtime.add_process(app).await;
+    }
+
+    let result = match runtime.start_all().await {
+        Ok(()) => {
+            info!("Supervisor started, waiting for shutdown signal");
+            tokio::select! {
+                signal = shutdown_signal() => {
+                    info!("Received {signal}, stopping every process");
+                    Ok(())
+                }
+                res = runtime.supervise(config.supervise_interval) => res,
+            }
+        }
+        Err(e) => Err(e),
+    };
+    let stopped = runtime.shutdown().await;
+    log::logger().flush();
+    result.and(stopped)
+}
@@ -0,0 +1,11 @@
+//! default app name shared by logger, sysdir and unixservice
+
+/// file stem of the running executable so the default follow the consuming binary,
+/// `env!("CARGO_PKG_NAME")` of the library would be its own name instead
+/// `fallback` is used when it cant be resolved, usually the caller crate name
+pub fn exe_name(fallback: &str) -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| fallback.to_string())
+}
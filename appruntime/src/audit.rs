@@ -0,0 +1,74 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::Serialize;
+
+use crate::AppProcess;
+
+/// log target of the lifecycle audit record, always emitted on info level
+/// so its kept even when debug logging is off
+pub const AUDIT_TARGET: &str = "appruntime::audit";
+
+/// lifecycle transition recorded in [`AuditRecord::action`], serialized lowercase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Start,
+    Stop,
+    Restart,
+    Update,
+    /// left running outside the runtime, see [`AppRuntime::detach_process`](crate::AppRuntime::detach_process)
+    Detach,
+}
+
+/// lifecycle transition written as single line json on [`AUDIT_TARGET`]
+/// ```text
+/// {"timestamp":1700000000,"action":"start","id":"web","command":"sh","args":["serve.sh"]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditRecord<'a> {
+    /// unix seconds
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub id: &'a str,
+    pub command: &'a str,
+    pub args: &'a [String],
+}
+
+impl<'a> AuditRecord<'a> {
+    fn new(action: AuditAction, app: &'a AppProcess) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            action,
+            id: &app.id,
+            command: &app.command,
+            args: &app.args,
+        }
+    }
+}
+
+pub(crate) fn audit(action: AuditAction, app: &AppProcess) {
+    if let Ok(json) = serde_json::to_string(&AuditRecord::new(action, app)) {
+        info!(target: AUDIT_TARGET, "{json}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_json_shape() {
+        let app = AppProcess::new("web", "sh", vec!["serve.sh".to_string()]);
+        let record = AuditRecord {
+            timestamp: 1700000000,
+            ..AuditRecord::new(AuditAction::Detach, &app)
+        };
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"timestamp":1700000000,"action":"detach","id":"web","command":"sh","args":["serve.sh"]}"#
+        );
+    }
+}
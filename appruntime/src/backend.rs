@@ -0,0 +1,125 @@
+use std::{
+    fmt::Debug,
+    io,
+    process::{ExitStatus, Stdio},
+};
+
+use tokio::process::{Child, Command};
+
+use crate::{AppError, AppProcess, AppRuntimeResult};
+
+/// running instance created by [`ProcessBackend::spawn`]
+/// e.g. local child, systemd unit or docker container
+#[async_trait::async_trait]
+pub trait ProcessHandle: Send + Sync + Debug {
+    async fn kill(&mut self) -> io::Result<()>;
+    /// None while its still running
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+    async fn wait(&mut self) -> io::Result<ExitStatus>;
+    /// os pid if it has one
+    fn id(&self) -> Option<u32> {
+        None
+    }
+    /// deliver unix signal, default to `kill` on its pid
+    /// unsupported without pid or on non unix
+    fn signal(&mut self, signal: i32) -> io::Result<()> {
+        #[cfg(unix)]
+        if let Some(pid) = self.id() {
+            if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            return Ok(());
+        }
+        let _ = signal;
+        Err(io::ErrorKind::Unsupported.into())
+    }
+    /// ask the process to exit, default to SIGTERM
+    fn terminate(&mut self) -> io::Result<()> {
+        #[cfg(unix)]
+        return self.signal(libc::SIGTERM);
+        #[cfg(not(unix))]
+        Err(io::ErrorKind::Unsupported.into())
+    }
+    /// let go of the process leaving it running, return its pid
+    /// default just drop the handle, backend killing on drop should override it
+    /// to stop doing so and hand off reaping the process
+    fn detach(self: Box<Self>) -> Option<u32> {
+        self.id()
+    }
+}
+
+/// how the runtime start a process, set per process with [`AppProcess::backend`]
+/// so one runtime can manage different kind of process with the same api
+#[async_trait::async_trait]
+pub trait ProcessBackend: Send + Sync + Debug {
+    /// start the process from its command and [`AppProcess::rendered_args`]
+    /// captured output should be pushed into [`AppProcess::output`] when its set
+    async fn spawn(&self, app: &AppProcess) -> AppRuntimeResult<Box<dyn ProcessHandle>>;
+}
+
+/// default backend, spawn child process on this machine
+/// the command is resolved through `PATH` first, failing with [`AppError::ExecutableNotFound`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LocalBackend;
+
+#[async_trait::async_trait]
+impl ProcessBackend for LocalBackend {
+    async fn spawn(&self, app: &AppProcess) -> AppRuntimeResult<Box<dyn ProcessHandle>> {
+        let program = which::which(&app.command).map_err(|_| AppError::ExecutableNotFound {
+            command: app.command.clone(),
+        })?;
+        let mut command = Command::new(program);
+        command.args(app.rendered_args()?).envs(&app.env);
+        if let Some(cwd) = &app.cwd {
+            command.current_dir(cwd);
+        }
+        if app.output.is_some() {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let mut child = command.spawn()?;
+        if let Some(output) = &app.output {
+            output.capture(&mut child);
+        }
+        Ok(Box::new(LocalHandle {
+            child,
+            kill_on_drop: true,
+        }))
+    }
+}
+
+/// child spawned by [`LocalBackend`]
+/// killed on drop so dropping the runtime without shutdown doesnt leak it, unless its detached
+#[derive(Debug)]
+struct LocalHandle {
+    child: Child,
+    kill_on_drop: bool,
+}
+
+impl Drop for LocalHandle {
+    fn drop(&mut self) {
+        if self.kill_on_drop && matches!(self.child.try_wait(), Ok(None)) {
+            self.child.start_kill().ok();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ProcessHandle for LocalHandle {
+    async fn kill(&mut self) -> io::Result<()> {
+        self.child.kill().await
+    }
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+    async fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait().await
+    }
+    fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+    /// dropped without killing, tokio reap it once it exit
+    fn detach(mut self: Box<Self>) -> Option<u32> {
+        self.kill_on_drop = false;
+        self.child.id()
+    }
+}
@@ -0,0 +1,127 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppProcess, RestartPolicy, UpdateMode};
+
+/// process definition written by hand in config file, converted with `AppProcess::try_from`
+/// ```toml
+/// [[process]]
+/// id = "web"
+/// command = "./server"
+/// args = ["--port", "8080", "--name={id}"]
+/// template_args = true
+/// env = { RUST_LOG = "info" }
+/// capture_output = 500
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppProcessConfig {
+    pub id: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// resolve `{id}`, `{env:NAME}` and runtime context placeholder in args
+    #[serde(default)]
+    pub template_args: bool,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// prefix on merged output, default to the id
+    #[serde(default)]
+    pub log_prefix: Option<String>,
+    /// forward captured line into the logger
+    #[serde(default)]
+    pub log_output: bool,
+    /// how the update step is run, default to appending `--update`
+    /// e.g. `update = { env = ["APP_MODE", "update"] }`
+    #[serde(default)]
+    pub update: Option<UpdateMode>,
+    /// marked running only once its listening on this localhost port
+    #[serde(default)]
+    pub ready_port: Option<u16>,
+    /// retry count of transient spawn failure
+    #[serde(default)]
+    pub spawn_retries: u32,
+    /// stopped once its running longer than this
+    #[serde(default)]
+    pub max_runtime: Option<Duration>,
+    /// captured line capacity, not captured when its None
+    #[serde(default)]
+    pub capture_output: Option<usize>,
+    /// exited process is left stopped when its None
+    #[serde(default)]
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+/// fail with [`AppError::InvalidConfig`] on empty id/command or malformed env key
+impl TryFrom<AppProcessConfig> for AppProcess {
+    type Error = AppError;
+
+    fn try_from(config: AppProcessConfig) -> Result<Self, Self::Error> {
+        let invalid = |reason: &str| AppError::InvalidConfig {
+            id: config.id.clone(),
+            reason: reason.to_string(),
+        };
+        if config.id.trim().is_empty() {
+            return Err(invalid("id is empty"));
+        }
+        if config.command.trim().is_empty() {
+            return Err(invalid("command is empty"));
+        }
+        if config
+            .env
+            .keys()
+            .any(|key| key.is_empty() || key.contains(['=', '\0']))
+        {
+            return Err(invalid("env key is empty or contain '=' or nul"));
+        }
+
+        let mut app = AppProcess::new(config.id, config.command, config.args);
+        app.template_args = config.template_args;
+        app.env = config.env;
+        app.cwd = config.cwd;
+        app.labels = config.labels;
+        app.depends_on = config.depends_on;
+        app.log_prefix = config.log_prefix;
+        app.log_output = config.log_output;
+        app.restart_policy = config.restart_policy;
+        app.update = config.update;
+        app.ready_port = config.ready_port;
+        app.spawn_retries = config.spawn_retries;
+        app.max_runtime = config.max_runtime;
+        if let Some(capacity) = config.capture_output {
+            app = app.capture_output(capacity);
+        }
+        Ok(app)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_required_fields() {
+        let config: AppProcessConfig = serde_json::from_str(
+            r#"{"id": "web", "command": "sh", "env": {"PORT": "8080"}, "capture_output": 10}"#,
+        )
+        .unwrap();
+        let app = AppProcess::try_from(config.clone()).unwrap();
+        assert_eq!(app.env["PORT"], "8080");
+        assert!(app.args.is_empty() && app.output.is_some());
+
+        let empty = AppProcessConfig {
+            command: " ".to_string(),
+            ..config
+        };
+        assert!(matches!(
+            AppProcess::try_from(empty),
+            Err(AppError::InvalidConfig { id, .. }) if id == "web"
+        ));
+    }
+}
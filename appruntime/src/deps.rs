@@ -0,0 +1,84 @@
+use indexmap::IndexMap;
+
+use crate::AppProcess;
+
+/// id of the process directly depending on `id`, in runtime order
+pub(crate) fn dependents(apps: &IndexMap<String, AppProcess>, id: &str) -> Vec<String> {
+    apps.iter()
+        .filter(|(_, app)| app.depends_on.iter().any(|dep| dep == id))
+        .map(|(dependent, _)| dependent.clone())
+        .collect()
+}
+
+/// `id` then its transitive dependents, each one after everything it depends on
+/// fail with the process forming the cycle
+pub(crate) fn restart_order(
+    apps: &IndexMap<String, AppProcess>,
+    id: &str,
+) -> Result<Vec<String>, Vec<String>> {
+    fn visit(
+        apps: &IndexMap<String, AppProcess>,
+        id: &str,
+        path: &mut Vec<String>,
+        done: &mut Vec<String>,
+    ) -> Result<(), Vec<String>> {
+        if let Some(start) = path.iter().position(|p| p == id) {
+            return Err(path[start..].to_vec());
+        }
+        if done.iter().any(|d| d == id) {
+            return Ok(());
+        }
+        path.push(id.to_string());
+        for dependent in dependents(apps, id) {
+            visit(apps, &dependent, path, done)?;
+        }
+        path.pop();
+        done.push(id.to_string());
+        Ok(())
+    }
+
+    let mut done = vec![];
+    visit(apps, id, &mut vec![], &mut done)?;
+    // post order put the dependents first
+    done.reverse();
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> IndexMap<String, AppProcess> {
+        edges
+            .iter()
+            .map(|(id, deps)| {
+                let app = deps
+                    .iter()
+                    .fold(AppProcess::new(id, "true", vec![]), |app, dep| {
+                        app.depends_on(dep)
+                    });
+                (id.to_string(), app)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dependents_restart_after_their_dependencies() {
+        let apps = graph(&[
+            ("web", &["api", "cache"]),
+            ("api", &["db"]),
+            ("cache", &["db"]),
+            ("db", &[]),
+            ("cron", &[]),
+        ]);
+        assert_eq!(dependents(&apps, "db"), ["api", "cache"]);
+        assert_eq!(
+            restart_order(&apps, "db").unwrap(),
+            ["db", "cache", "api", "web"]
+        );
+        assert_eq!(restart_order(&apps, "cron").unwrap(), ["cron"]);
+
+        let cyclic = graph(&[("a", &["b"]), ("b", &["a"])]);
+        assert_eq!(restart_order(&cyclic, "a").unwrap_err(), ["a", "b"]);
+    }
+}
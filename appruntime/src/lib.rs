@@ -1,11 +1,56 @@
+use futures_util::future::join_all;
 use indexmap::IndexMap;
 use log::{debug, error, warn};
-use std::{fmt::Debug, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::Debug,
+    net::Ipv4Addr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::{
-    process::{Child, Command},
-    sync::RwLock,
-    task::spawn_blocking,
+    net::TcpStream,
+    process::Command,
+    sync::{broadcast, RwLock},
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+
+mod audit;
+mod backend;
+mod config;
+mod deps;
+mod output;
+mod policy;
+#[cfg(feature = "pty")]
+mod pty;
+mod template;
+mod update;
+use audit::audit;
+pub use audit::{AuditAction, AuditRecord, AUDIT_TARGET};
+pub use backend::{LocalBackend, ProcessBackend, ProcessHandle};
+pub use config::AppProcessConfig;
+pub use output::{
+    prefix_line, OutputBuffer, TailStart, DEFAULT_MAX_BYTES, OUTPUT_STREAM_CAPACITY, OUTPUT_TARGET,
 };
+use output::{Forward, TaggedLine};
+pub use policy::{RestartAction, RestartPolicy, DEFAULT_RESET_AFTER};
+#[cfg(feature = "pty")]
+pub use pty::{PtyBackend, PtyHandle};
+pub use template::{render, TemplateContext};
+pub use update::{UpdateMode, DEFAULT_UPDATE_ARGS};
+
+pub use appflow_std::runtime::{aggregate_exit_code, exit_code, to_exit_code, ProcessManager};
 
 pub trait AppResult<T, E> {
     /// log error
@@ -42,7 +87,18 @@ where
 
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// why the runtime stopped the process, None when it exited on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StopReason {
+    /// stopped through the runtime api
+    Requested,
+    /// ran longer than [`AppProcess::max_runtime`]
+    Timeout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProcessStatus {
     Running,
     #[default]
@@ -63,21 +119,281 @@ impl std::ops::Not for ProcessStatus {
 pub struct AppProcess {
     pub id: String,
     pub command: String,
-    pub process: Option<Child>,
+    pub process: Option<Box<dyn ProcessHandle>>,
     pub status: ProcessStatus,
     pub args: Vec<String>,
+    /// resolve placeholder in args at spawn time, see [`AppProcess::rendered_args`]
+    pub template_args: bool,
+    /// placeholder values shared by the runtime, attached when its added to [`AppRuntime`]
+    pub context: Option<TemplateContext>,
+    /// extra environment variable on top of the inherited one
+    pub env: BTreeMap<String, String>,
+    /// working directory, default to the runtime one
+    pub cwd: Option<PathBuf>,
+    /// captured stdout/stderr, only when enabled with [`AppProcess::capture_output`]
+    pub output: Option<Arc<OutputBuffer>>,
+    /// tags to operate process as a group, e.g. "web", "workers"
+    pub labels: Vec<String>,
+    /// id of the process this one need, used by [`AppRuntime::to_dot`]
+    /// and [`AppRuntime::restart_with_dependents`], the start order isnt enforced by it
+    pub depends_on: Vec<String>,
+    /// how many times the process has been restarted by the runtime
+    pub restart_count: u32,
+    /// consecutive restart by the supervisor, checked by the [`RestartPolicy`] retry budget
+    /// and backoff. reset once it ran past [`RestartPolicy::reset_after`]
+    pub crash_count: u32,
+    /// prefix of its line on merged output, default to the id
+    pub log_prefix: Option<String>,
+    /// forward captured line into the logger with [`OUTPUT_TARGET`]
+    pub log_output: bool,
+    /// command and its args run to completion before respawn on restart
+    /// e.g. clearing lock file or running migration
+    pub pre_restart: Option<Vec<String>>,
+    /// default to [`DEFAULT_HOOK_TIMEOUT`]
+    pub pre_restart_timeout: Option<Duration>,
+    /// how the update step is run, default to appending [`DEFAULT_UPDATE_ARGS`]
+    pub update: Option<UpdateMode>,
+    /// user metadata kept with the process, e.g. config struct or handle
+    pub userdata: Option<Box<dyn Any + Send + Sync>>,
+    /// where the process run, default to [`LocalBackend`]
+    pub backend: Option<Arc<dyn ProcessBackend>>,
+    /// how long spawning may take, default to [`DEFAULT_START_TIMEOUT`]
+    /// include waiting for [`AppProcess::ready_port`]
+    pub start_timeout: Option<Duration>,
+    /// only mark it running once it accept tcp connection on this localhost port
+    pub ready_port: Option<u16>,
+    /// how many times transient spawn failure is retried, see [`AppError::is_transient`]
+    pub spawn_retries: u32,
+    /// stopped by [`AppRuntime::supervise`] once its running longer than this, e.g. batch job
+    /// always with SIGTERM first, waiting the stop grace or [`DEFAULT_STOP_GRACE`]
+    pub max_runtime: Option<Duration>,
+    /// why it was last stopped by the runtime, reset on spawn
+    pub stop_reason: Option<StopReason>,
+    /// how long killing may take, default to [`DEFAULT_STOP_TIMEOUT`]
+    pub stop_timeout: Option<Duration>,
+    /// when set stopping send SIGTERM and wait this long before killing it
+    /// e.g. database style process corrupting its state on hard kill
+    pub stop_grace: Option<Duration>,
+    /// when the process was last spawned
+    pub started_at: Option<Instant>,
+    /// when the restart delayed by [`RestartPolicy::backoff`] is due
+    pub restart_at: Option<Instant>,
+    /// consulted by [`AppRuntime::supervise`] when the process exit
+    /// exited process is left stopped when its None
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+/// definition of [`AppProcess`] without its running handle, from [`AppRuntime::snapshot`]
+/// userdata and backend can't be serialized so they are left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppProcessDef {
+    pub id: String,
+    pub command: String,
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub template_args: bool,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub log_prefix: Option<String>,
+    #[serde(default)]
+    pub log_output: bool,
+    /// line capacity and max bytes of the output capture
+    #[serde(default)]
+    pub capture_output: Option<(usize, usize)>,
+    #[serde(default)]
+    pub pre_restart: Option<Vec<String>>,
+    #[serde(default)]
+    pub pre_restart_timeout: Option<Duration>,
+    #[serde(default)]
+    pub update: Option<UpdateMode>,
+    #[serde(default)]
+    pub start_timeout: Option<Duration>,
+    #[serde(default)]
+    pub ready_port: Option<u16>,
+    #[serde(default)]
+    pub spawn_retries: u32,
+    #[serde(default)]
+    pub max_runtime: Option<Duration>,
+    #[serde(default)]
+    pub stop_timeout: Option<Duration>,
+    #[serde(default)]
+    pub stop_grace: Option<Duration>,
+    #[serde(default)]
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+impl From<&AppProcess> for AppProcessDef {
+    fn from(app: &AppProcess) -> Self {
+        Self {
+            id: app.id.clone(),
+            command: app.command.clone(),
+            args: app.args.clone(),
+            template_args: app.template_args,
+            env: app.env.clone(),
+            cwd: app.cwd.clone(),
+            labels: app.labels.clone(),
+            depends_on: app.depends_on.clone(),
+            log_prefix: app.log_prefix.clone(),
+            log_output: app.log_output,
+            capture_output: app.output.as_ref().map(|o| (o.capacity(), o.max_bytes())),
+            pre_restart: app.pre_restart.clone(),
+            pre_restart_timeout: app.pre_restart_timeout,
+            update: app.update.clone(),
+            start_timeout: app.start_timeout,
+            ready_port: app.ready_port,
+            spawn_retries: app.spawn_retries,
+            max_runtime: app.max_runtime,
+            stop_timeout: app.stop_timeout,
+            stop_grace: app.stop_grace,
+            restart_policy: app.restart_policy.clone(),
+        }
+    }
+}
+
+impl From<AppProcessDef> for AppProcess {
+    fn from(def: AppProcessDef) -> Self {
+        let mut app = AppProcess::new(def.id, def.command, def.args);
+        app.env = def.env;
+        app.cwd = def.cwd;
+        app.labels = def.labels;
+        app.depends_on = def.depends_on;
+        app.template_args = def.template_args;
+        app.log_prefix = def.log_prefix;
+        app.log_output = def.log_output;
+        if let Some((capacity, max_bytes)) = def.capture_output {
+            app = app.capture_output_with_limits(capacity, max_bytes);
+        }
+        app.pre_restart = def.pre_restart;
+        app.pre_restart_timeout = def.pre_restart_timeout;
+        app.start_timeout = def.start_timeout;
+        app.stop_timeout = def.stop_timeout;
+        app.stop_grace = def.stop_grace;
+        app.restart_policy = def.restart_policy;
+        app.update = def.update;
+        app.ready_port = def.ready_port;
+        app.spawn_retries = def.spawn_retries;
+        app.max_runtime = def.max_runtime;
+        app
+    }
 }
 
+/// how long the pre restart hook may run when no timeout is set
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// wait before the first spawn retry, doubled on every next one
+pub const SPAWN_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// how long spawning the process may take when no start timeout is set
+pub const DEFAULT_START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// how long killing the process may take when no stop timeout is set
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// SIGTERM grace of process stopped over its max runtime when no stop grace is set
+pub const DEFAULT_STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// how often [`AppRuntime::wait_for_status`] check the process
+pub const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Failed to find the process/executable on the runtime given id: {0}")]
     NotFound(String),
     #[error("Failed to execute command : {0}")]
     SubProcess(#[from] std::io::Error),
+    #[error("Executable not found in PATH: {command}")]
+    ExecutableNotFound { command: String },
+    #[error("Dependency cycle between processes: {0:?}")]
+    DependencyCycle(Vec<String>),
+    #[error("Process {0} is not running")]
+    NotRunning(String),
+    #[error("Process {id} already restarted {restarts} times, its restart policy gave up")]
+    RestartsExhausted { id: String, restarts: u32 },
+    #[error("Output capture is not enabled for process: {0}")]
+    NoCapture(String),
+    #[error("Pre restart hook of process {0} failed: {1}")]
+    PreRestart(String, String),
+    #[error("Process {id} didnt start in time")]
+    StartTimeout { id: String },
+    #[error("Process {id} exited with {status} before listening on port {port}")]
+    ExitedBeforeReady {
+        id: String,
+        port: u16,
+        status: String,
+    },
+    #[error("Process {id} exited with {status} right after start")]
+    CrashedOnStart { id: String, status: String },
+    #[error("Process {id} didnt stop in time")]
+    StopTimeout { id: String },
+    #[error("Invalid config of process {id}: {reason}")]
+    InvalidConfig { id: String, reason: String },
+    #[error("Update command of process {0} failed: {1}")]
+    Update(String, String),
+    #[error("Process {0} didnt reach {1:?} status in time")]
+    StatusTimeout(String, ProcessStatus),
+    /// every process that failed when applying to many of them, e.g. reconcile or stop all
+    #[error("Failed on process {:?}", .0.iter().map(|(id, _)| id).collect::<Vec<_>>())]
+    Reconcile(Vec<(String, AppError)>),
+}
+
+impl AppError {
+    /// spawn failure worth retrying, e.g. EAGAIN under fork pressure or busy executable
+    /// missing executable or permission denied are permanent
+    pub fn is_transient(&self) -> bool {
+        let AppError::SubProcess(e) = self else {
+            return false;
+        };
+        #[cfg(unix)]
+        if matches!(
+            e.raw_os_error(),
+            Some(libc::EAGAIN | libc::ETXTBSY | libc::EINTR)
+        ) {
+            return true;
+        }
+        matches!(
+            e.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted
+        )
+    }
 }
 
 pub type AppRuntimeResult<T> = Result<T, AppError>;
 
+/// status of single process returned by [`AppRuntime::list_info`]
+/// the json field are kept stable for script and http api
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProcessInfo {
+    pub id: String,
+    pub status: ProcessStatus,
+    pub pid: Option<u32>,
+    /// None when its not running
+    pub uptime_secs: Option<u64>,
+    pub restart_count: u32,
+    /// only present once the runtime stopped it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+}
+
+impl From<&AppProcess> for ProcessInfo {
+    fn from(app: &AppProcess) -> Self {
+        Self {
+            id: app.id.clone(),
+            status: app.status.clone(),
+            pid: app.pid(),
+            uptime_secs: app.uptime().map(|uptime| uptime.as_secs()),
+            restart_count: app.restart_count,
+            stop_reason: app.stop_reason,
+        }
+    }
+}
+
 impl AppProcess {
     pub fn new(id: impl ToString, command: impl ToString, args: Vec<String>) -> Self {
         Self {
@@ -87,30 +403,455 @@ impl AppProcess {
             ..Default::default()
         }
     }
+
+    pub fn env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn cwd(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    pub fn label(mut self, label: impl ToString) -> Self {
+        self.labels.push(label.to_string());
+        self
+    }
+
+    /// record that this process need `id`, see [`AppProcess::depends_on`]
+    pub fn depends_on(mut self, id: impl ToString) -> Self {
+        self.depends_on.push(id.to_string());
+        self
+    }
+
+    pub fn has_label(&self, label: &str) -> bool {
+        self.labels.iter().any(|l| l == label)
+    }
+
+    /// args appended to run the update step instead of `--update`, e.g. `["migrate"]`
+    pub fn update_args(mut self, args: Vec<String>) -> Self {
+        self.update = Some(UpdateMode::Args(args));
+        self
+    }
+
+    /// run the update step with the env var instead of the update args, see [`UpdateMode::Env`]
+    pub fn update_env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.update = Some(UpdateMode::Env(key.to_string(), value.to_string()));
+        self
+    }
+
+    /// run separate command as the update step, see [`UpdateMode::Command`]
+    pub fn update_command(mut self, command: Vec<String>) -> Self {
+        self.update = Some(UpdateMode::Command(command));
+        self
+    }
+
+    /// run `command` before every restart, the restart is aborted if it fail
+    pub fn pre_restart(mut self, command: Vec<String>, timeout: Option<Duration>) -> Self {
+        self.pre_restart = Some(command);
+        self.pre_restart_timeout = timeout;
+        self
+    }
+
+    /// run the process with other backend instead of [`LocalBackend`]
+    /// e.g. systemd unit or docker container
+    pub fn backend(mut self, backend: impl ProcessBackend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// spawn the process attached to pseudo terminal, for tool checking `isatty`
+    /// shorthand of `.backend(PtyBackend)`, plain pipe stay the default
+    #[cfg(feature = "pty")]
+    pub fn with_pty(self) -> Self {
+        self.backend(PtyBackend)
+    }
+
+    /// mark it running only once its listening on the localhost `port` instead of right after spawn
+    /// since the start wait for it, process started after this one see the port already live
+    pub fn ready_port(mut self, port: u16) -> Self {
+        self.ready_port = Some(port);
+        self
+    }
+
+    /// force stop it gracefully once its running longer than `max`, checked by [`AppRuntime::supervise`]
+    /// SIGTERM is sent first even without [`AppProcess::stop_grace`], see [`DEFAULT_STOP_GRACE`]
+    /// marked with [`StopReason::Timeout`] and not restarted
+    pub fn max_runtime(mut self, max: Duration) -> Self {
+        self.max_runtime = Some(max);
+        self
+    }
+
+    /// retry spawning up to `retries` times on transient failure, waiting
+    /// [`SPAWN_RETRY_BACKOFF`] doubled on every attempt, all within the start timeout
+    pub fn spawn_retries(mut self, retries: u32) -> Self {
+        self.spawn_retries = retries;
+        self
+    }
+
+    /// bound how long spawning and killing the process may take
+    pub fn timeouts(mut self, start: Duration, stop: Duration) -> Self {
+        self.start_timeout = Some(start);
+        self.stop_timeout = Some(stop);
+        self
+    }
+
+    /// prefix shown on [`AppRuntime::merged_output`] and the logger instead of the id
+    pub fn log_prefix(mut self, prefix: impl ToString) -> Self {
+        self.log_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// forward captured line into the logger prefixed with [`AppProcess::log_prefix`]
+    /// the prefix is colored when stdout is a terminal, only work with output capture
+    pub fn log_output(mut self) -> Self {
+        self.log_output = true;
+        self
+    }
+
+    /// stop with SIGTERM first, see [`AppProcess::stop_grace`]
+    pub fn stop_grace(mut self, grace: Duration) -> Self {
+        self.stop_grace = Some(grace);
+        self
+    }
+
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = Some(policy);
+        self
+    }
+
+    /// attach user metadata, replacing the previous one
+    pub fn with_userdata<T: Any + Send + Sync>(mut self, data: T) -> Self {
+        self.userdata = Some(Box::new(data));
+        self
+    }
+
+    /// None if nothing attached or its not a `T`
+    pub fn userdata<T: Any>(&self) -> Option<&T> {
+        self.userdata.as_ref()?.downcast_ref()
+    }
+
+    pub fn userdata_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.userdata.as_mut()?.downcast_mut()
+    }
+
+    /// pipe stdout/stderr into buffer keeping the last `capacity` lines
+    /// and at most [`DEFAULT_MAX_BYTES`]
+    pub fn capture_output(mut self, capacity: usize) -> Self {
+        self.output = Some(Arc::new(OutputBuffer::new(capacity)));
+        self
+    }
+
+    /// same as [`AppProcess::capture_output`] with explicit total bytes limit
+    pub fn capture_output_with_limits(mut self, capacity: usize, max_bytes: usize) -> Self {
+        self.output = Some(Arc::new(OutputBuffer::with_limits(capacity, max_bytes)));
+        self
+    }
+
+    /// time since it was spawned, None when its not running
+    pub fn uptime(&self) -> Option<Duration> {
+        self.started_at
+            .filter(|_| self.status == ProcessStatus::Running)
+            .map(|at| at.elapsed())
+    }
+
+    /// os pid of the process, None when its not running
+    pub fn pid(&self) -> Option<u32> {
+        self.process
+            .as_ref()
+            .filter(|_| self.status == ProcessStatus::Running)
+            .and_then(|p| p.id())
+    }
+
+    /// resolve the args with [`render`] when [`AppProcess::template_args`] is set
+    /// - `{id}` the process id
+    /// - `{env:NAME}` the process env then the inherited one
+    /// - `{name}` value set with [`AppRuntime::set_context`]
+    ///
+    /// `{{` and `}}` are literal brace, unknown placeholder fail with [`AppError::InvalidConfig`]
+    /// ```ignore
+    /// runtime.set_context("config_dir", "/etc/fleet");
+    /// AppProcess::new("w1", "./worker", vec!["--config={config_dir}/{id}.toml".into()]).template_args();
+    /// ```
+    pub fn rendered_args(&self) -> AppRuntimeResult<Vec<String>> {
+        if !self.template_args {
+            return Ok(self.args.clone());
+        }
+        let lookup = |name: &str| match name.strip_prefix("env:") {
+            Some(key) => self
+                .env
+                .get(key)
+                .cloned()
+                .or_else(|| std::env::var(key).ok()),
+            None if name == "id" => Some(self.id.clone()),
+            None => self.context.as_ref()?.get(name),
+        };
+        self.args
+            .iter()
+            .map(|arg| {
+                render(arg, lookup).map_err(|reason| AppError::InvalidConfig {
+                    id: self.id.clone(),
+                    reason,
+                })
+            })
+            .collect()
+    }
+
+    /// resolve placeholder in args at spawn time, see [`AppProcess::rendered_args`]
+    pub fn template_args(mut self) -> Self {
+        self.template_args = true;
+        self
+    }
+
+    /// check if both process would be spawned and updated the same way, comparing
+    /// command, args, template args, env, cwd, backend, whether output is captured,
+    /// ready port, start timeout, spawn retries and update mode.
+    /// backend is compared by its `Debug`, unset is [`LocalBackend`].
+    /// the rest (labels, restart policy, stop setting, capture limits..) apply without respawn
+    pub fn same_spec(&self, other: &AppProcess) -> bool {
+        let backend = |app: &AppProcess| match &app.backend {
+            Some(backend) => format!("{backend:?}"),
+            None => format!("{LocalBackend:?}"),
+        };
+        self.command == other.command
+            && self.args == other.args
+            && self.template_args == other.template_args
+            && self.env == other.env
+            && self.cwd == other.cwd
+            && backend(self) == backend(other)
+            && self.output.is_some() == other.output.is_some()
+            && self.ready_port == other.ready_port
+            && self.start_timeout == other.start_timeout
+            && self.spawn_retries == other.spawn_retries
+            && self.update == other.update
+    }
+
+    /// take the runtime state of the process this one replace, the output buffer is kept
+    /// when the capture limits didnt change otherwise its lines are copied into the new one
+    fn inherit(&mut self, old: &mut AppProcess) {
+        self.restart_count = old.restart_count;
+        self.crash_count = old.crash_count;
+        self.restart_at = old.restart_at.take();
+        self.stop_reason = old.stop_reason.take();
+        match (&self.output, old.output.take()) {
+            (Some(new), Some(old))
+                if new.capacity() == old.capacity() && new.max_bytes() == old.max_bytes() =>
+            {
+                self.output = Some(old);
+            }
+            (Some(new), Some(old)) => old.lines().into_iter().for_each(|line| new.push(line)),
+            _ => {}
+        }
+    }
+
+    /// copy of everything spawning read, so it can be spawned without holding the runtime lock
+    /// the handle and userdata arent cloned, the output buffer is shared
+    fn spawn_spec(&self) -> AppProcess {
+        AppProcess {
+            id: self.id.clone(),
+            command: self.command.clone(),
+            args: self.args.clone(),
+            template_args: self.template_args,
+            context: self.context.clone(),
+            env: self.env.clone(),
+            cwd: self.cwd.clone(),
+            output: self.output.clone(),
+            labels: self.labels.clone(),
+            depends_on: self.depends_on.clone(),
+            log_prefix: self.log_prefix.clone(),
+            log_output: self.log_output,
+            backend: self.backend.clone(),
+            start_timeout: self.start_timeout,
+            ready_port: self.ready_port,
+            spawn_retries: self.spawn_retries,
+            update: self.update.clone(),
+            ..Default::default()
+        }
+    }
+
+    async fn spawn(&mut self) -> AppRuntimeResult<()> {
+        let process = self.launch().await?;
+        self.running(process);
+        Ok(())
+    }
+
+    fn running(&mut self, process: Box<dyn ProcessHandle>) {
+        self.process = Some(process);
+        self.started_at = Some(Instant::now());
+        self.restart_at = None;
+        self.stop_reason = None;
+        self.status = ProcessStatus::Running;
+    }
+
+    /// spawn the process and wait until its ready, leaving its state untouched
+    async fn launch(&self) -> AppRuntimeResult<Box<dyn ProcessHandle>> {
+        let backend = self
+            .backend
+            .clone()
+            .unwrap_or_else(|| Arc::new(LocalBackend));
+        let timeout = self.start_timeout.unwrap_or(DEFAULT_START_TIMEOUT);
+        let deadline = Instant::now() + timeout;
+        if let Some(output) = &self.output {
+            output.start_run();
+        }
+        let spawn = async {
+            let mut delay = SPAWN_RETRY_BACKOFF;
+            for attempt in 1..=self.spawn_retries {
+                match backend.spawn(self).await {
+                    Err(e) if e.is_transient() => {
+                        let (id, max) = (&self.id, self.spawn_retries);
+                        debug!(
+                            "Spawning Process {id} failed: {e}, retry {attempt}/{max} in {delay:?}"
+                        );
+                        sleep(delay).await;
+                        delay = delay.saturating_mul(2);
+                    }
+                    res => return res,
+                }
+            }
+            backend.spawn(self).await
+        };
+        let mut process = match tokio::time::timeout(timeout, spawn).await {
+            Ok(process) => process.log()?,
+            Err(_) => {
+                let id = self.id.clone();
+                return Err(AppError::StartTimeout { id }).log();
+            }
+        };
+        if let Err(e) = self.wait_ready(&mut process, deadline).await {
+            process.kill().await.ok();
+            return Err(e).log();
+        }
+        Ok(process)
+    }
+
+    /// poll tcp connect to [`AppProcess::ready_port`] until it succeed, failing early if it exit
+    async fn wait_ready(
+        &self,
+        process: &mut Box<dyn ProcessHandle>,
+        deadline: Instant,
+    ) -> AppRuntimeResult<()> {
+        let Some(port) = self.ready_port else {
+            return Ok(());
+        };
+        let id = self.id.clone();
+        loop {
+            let connect = TcpStream::connect((Ipv4Addr::LOCALHOST, port));
+            if let Ok(Ok(_)) = tokio::time::timeout_at(deadline, connect).await {
+                debug!("Process {id} is listening on port {port}");
+                return Ok(());
+            }
+            if let Some(status) = process.try_wait()? {
+                let status = status.to_string();
+                return Err(AppError::ExitedBeforeReady { id, port, status });
+            }
+            if Instant::now() >= deadline {
+                return Err(AppError::StartTimeout { id });
+            }
+            sleep(STATUS_POLL_INTERVAL).await;
+        }
+    }
 }
 
 /// To start runtime application to handle multiple process
 /// can be used with UI
 /// using tokio
+/// call [`AppRuntime::shutdown`] before dropping it, drop only kill the children
+/// without the graceful stop, async cleanup can't run on drop
 type MyRuntime = Arc<RwLock<IndexMap<String, AppProcess>>>;
 pub struct AppRuntime {
     pub apps: MyRuntime,
+    shut_down: AtomicBool,
+    output_tx: broadcast::Sender<TaggedLine>,
+    context: TemplateContext,
 }
 
 impl Default for AppRuntime {
     fn default() -> Self {
         Self {
             apps: Arc::new(RwLock::new(IndexMap::new())),
+            shut_down: AtomicBool::new(false),
+            output_tx: broadcast::channel(OUTPUT_STREAM_CAPACITY).0,
+            context: TemplateContext::default(),
         }
     }
 }
 
 impl AppRuntime {
-    pub async fn add_process(&self, app: AppProcess) {
+    /// stopped runtime with the process definitions, e.g. from [`AppRuntime::snapshot`]
+    /// start it with [`AppRuntime::start_all`]
+    pub fn from_snapshot(defs: Vec<AppProcessDef>) -> Self {
+        let mut runtime = Self::default();
+        for def in defs {
+            let mut app = AppProcess::from(def);
+            runtime.attach(&mut app);
+            // not shared yet, no one else hold the lock
+            let apps = Arc::get_mut(&mut runtime.apps).expect("new runtime");
+            apps.get_mut().insert(app.id.clone(), app);
+        }
+        runtime
+    }
+
+    /// share the template context with the process and feed its captured output
+    /// into [`AppRuntime::output_stream`] and the logger when [`AppProcess::log_output`] is set
+    fn attach(&self, app: &mut AppProcess) {
+        app.context = Some(self.context.clone());
+        if let Some(output) = &app.output {
+            output.forward(Forward {
+                id: app.id.clone(),
+                prefix: app.log_prefix.clone().unwrap_or_else(|| app.id.clone()),
+                log: app.log_output,
+                tx: self.output_tx.clone(),
+            });
+        }
+    }
+
+    /// value of `{key}` in templated args, applied on the next spawn
+    pub fn set_context(&self, key: impl ToString, value: impl ToString) {
+        self.context.set(key.to_string(), value.to_string());
+    }
+
+    pub fn remove_context(&self, key: impl AsRef<str>) -> Option<String> {
+        self.context.remove(key.as_ref())
+    }
+
+    fn tagged_lines(&self) -> impl Stream<Item = TaggedLine> {
+        BroadcastStream::new(self.output_tx.subscribe()).filter_map(|line| match line {
+            Ok(line) => Some(line),
+            Err(BroadcastStreamRecvError::Lagged(n)) => {
+                warn!("Output stream lagging, skipped {n} lines");
+                None
+            }
+        })
+    }
+
+    /// captured output lines of every process tagged with its id, like `docker compose logs -f`
+    /// only new lines are streamed, lines missed by lagging subscriber are skipped with warning
+    pub fn output_stream(&self) -> impl Stream<Item = (String, String)> {
+        self.tagged_lines().map(|tagged| (tagged.id, tagged.line))
+    }
+
+    /// same as [`AppRuntime::output_stream`] but already rendered with [`prefix_line`]
+    /// using [`AppProcess::log_prefix`], colored when `color`
+    pub fn merged_output(&self, color: bool) -> impl Stream<Item = String> {
+        self.tagged_lines()
+            .map(move |tagged| prefix_line(&tagged.prefix, &tagged.line, color))
+    }
+
+    /// definition of every process in order, for blue/green style reload
+    /// build the new runtime with [`AppRuntime::from_snapshot`], check it then stop the old one
+    pub async fn snapshot(&self) -> Vec<AppProcessDef> {
+        let apps = self.apps.read().await;
+        apps.values().map(AppProcessDef::from).collect()
+    }
+
+    pub async fn add_process(&self, mut app: AppProcess) {
         debug!("Adding Process {}", app.id);
 
         let id = app.id.clone();
 
+        self.attach(&mut app);
         let mut process = self.apps.write().await;
         process.insert(app.id.clone(), app);
         debug!("Added Process {id} to runtime");
@@ -119,84 +860,178 @@ impl AppRuntime {
         debug!("Adding Process {}", app.id);
 
         let id = app.id.clone();
-        let child = Command::new(app.command.clone())
-            .args(app.args.clone())
-            .spawn()
-            .log()?;
-        debug!("Starting Process {id}");
-        app.process = Some(child);
-        app.status = ProcessStatus::Running;
+        self.attach(&mut app);
+        Self::start(&mut app, &id).await?;
 
         let mut process = self.apps.write().await;
         process.insert(app.id.clone(), app);
         Ok(())
     }
+    /// same as [`AppRuntime::add_process_then_run`] but check the process is still alive after
+    /// `settle`, failing with [`AppError::CrashedOnStart`] without adding it when it already exited
+    /// e.g. bad config or missing library
+    pub async fn start_with_healthcheck(
+        &self,
+        mut app: AppProcess,
+        settle: Duration,
+    ) -> AppRuntimeResult<()> {
+        let id = app.id.clone();
+        self.attach(&mut app);
+        Self::start(&mut app, &id).await?;
+        sleep(settle).await;
+        if let Some(process) = &mut app.process {
+            if let Some(status) = process.try_wait().log()? {
+                app.status = ProcessStatus::Stopped;
+                let status = status.to_string();
+                return Err(AppError::CrashedOnStart { id, status }).log();
+            }
+        }
+        debug!("Process {id} still running after {settle:?}");
+        self.apps.write().await.insert(id, app);
+        Ok(())
+    }
+
     pub async fn add_batch(&self, apps: Vec<AppProcess>) {
         for app in apps {
             self.add_process(app).await;
         }
     }
 
+    async fn start(app: &mut AppProcess, id: &str) -> AppRuntimeResult<()> {
+        debug!("Starting Process {id}");
+        app.spawn().await?;
+        audit(AuditAction::Start, app);
+        Ok(())
+    }
+
+    /// run the pre restart hook to completion bounded by its timeout
+    async fn pre_restart(app: &AppProcess, id: &str) -> AppRuntimeResult<()> {
+        let Some((program, args)) = app.pre_restart.as_ref().and_then(|c| c.split_first()) else {
+            return Ok(());
+        };
+        debug!("Running pre restart hook of Process {id}");
+        let hook_err = |e: String| AppError::PreRestart(id.to_string(), e);
+
+        let mut child = Command::new(program).args(args).spawn().log()?;
+        let timeout = app.pre_restart_timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT);
+        let err = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => {
+                let status = status.log()?;
+                if status.success() {
+                    return Ok(());
+                }
+                hook_err(format!("exited with {status}"))
+            }
+            Err(_) => {
+                child.kill().await.ok();
+                hook_err(format!("timed out after {timeout:?}"))
+            }
+        };
+        Err(err).log()
+    }
+
+    /// run the update step of the process to completion, the restart is aborted if it fail
+    /// its output goes into the captured output when enabled
+    async fn run_update(app: &mut AppProcess, id: &str) -> AppRuntimeResult<()> {
+        let mut command = UpdateMode::command(app.update.as_ref(), app)?;
+        let status = match &app.output {
+            Some(buffer) => {
+                let output = command.output().await.log()?;
+                for stream in [&output.stdout, &output.stderr] {
+                    for line in String::from_utf8_lossy(stream).lines() {
+                        buffer.push(line.to_string());
+                    }
+                }
+                output.status
+            }
+            None => command.spawn().log()?.wait().await?,
+        };
+        if !status.success() {
+            app.status = ProcessStatus::Stopped;
+            return Err(AppError::Update(id.to_string(), status.to_string())).log();
+        }
+        Ok(())
+    }
+
     async fn restart(app: &mut AppProcess, id: &str) -> AppRuntimeResult<()> {
         debug!("Restarting Process {id}");
-        if app.status == ProcessStatus::Running {
-            if let Some(process) = &mut app.process {
-                process.kill().await.log()?;
-            }
-        }
-        let mut args = app.args.clone();
-        args.push("--update".to_string());
-        // run process with update flag
-        Command::new(app.command.clone())
-            .args(args)
-            .spawn()
-            .log()?
-            .wait()
-            .await?;
-
-        let child = Command::new(app.command.clone())
-            .args(app.args.clone())
-            .spawn()
-            .log()?;
-        app.process = Some(child);
-        app.status = ProcessStatus::Running;
+        Self::kill(app, id).await?;
+        if let Err(e) = Self::pre_restart(app, id).await {
+            app.status = ProcessStatus::Stopped;
+            return Err(e);
+        }
+        Self::run_update(app, id).await?;
+        app.spawn().await?;
+        app.restart_count += 1;
+        audit(AuditAction::Restart, app);
         debug!("Succesfully Restarting Process {id}");
         Ok(())
     }
 
+    /// kill the running process, bounded by its stop timeout
+    async fn kill(app: &mut AppProcess, id: &str) -> AppRuntimeResult<()> {
+        let Some(process) = app
+            .process
+            .as_mut()
+            .filter(|_| app.status == ProcessStatus::Running)
+        else {
+            return Ok(());
+        };
+        let timeout = app.stop_timeout.unwrap_or(DEFAULT_STOP_TIMEOUT);
+        match tokio::time::timeout(timeout, process.kill()).await {
+            Ok(res) => Ok(res.log()?),
+            Err(_) => Err(AppError::StopTimeout { id: id.to_string() }).log(),
+        }
+    }
+
+    /// SIGTERM then wait up to `grace` for the exit before falling back to kill
+    async fn terminate(app: &mut AppProcess, id: &str, grace: Duration) -> AppRuntimeResult<()> {
+        let Some(process) = app
+            .process
+            .as_mut()
+            .filter(|_| app.status == ProcessStatus::Running)
+        else {
+            return Ok(());
+        };
+        if process.terminate().log_warn().is_ok() {
+            if let Ok(res) = tokio::time::timeout(grace, process.wait()).await {
+                res.log()?;
+                return Ok(());
+            }
+            warn!("Process {id} still running {grace:?} after SIGTERM, killing it");
+        }
+        Self::kill(app, id).await
+    }
+
     async fn stop(app: &mut AppProcess, id: &str) -> AppRuntimeResult<()> {
+        Self::stop_with(app, id, app.stop_grace).await
+    }
+
+    async fn stop_with(
+        app: &mut AppProcess,
+        id: &str,
+        grace: Option<Duration>,
+    ) -> AppRuntimeResult<()> {
+        // stopped by hand, cancel pending restart
+        app.restart_at = None;
         if app.status == ProcessStatus::Running {
-            if let Some(process) = &mut app.process {
-                process.kill().await.log()?;
+            match grace {
+                Some(grace) => Self::terminate(app, id, grace).await?,
+                None => Self::kill(app, id).await?,
             }
             app.status = ProcessStatus::Stopped;
+            app.stop_reason = Some(StopReason::Requested);
+            audit(AuditAction::Stop, app);
             debug!("Stopped Process {id}");
         }
         Ok(())
     }
 
     async fn ver_update(app: &mut AppProcess, id: &str) -> AppRuntimeResult<()> {
-        if app.status == ProcessStatus::Running {
-            if let Some(process) = &mut app.process {
-                process.kill().await.log()?;
-            }
-        }
-        let mut args = app.args.clone();
-        args.push("--update".to_string());
-        // run process with update flag
-        Command::new(app.command.clone())
-            .args(args)
-            .spawn()
-            .log()?
-            .wait()
-            .await?;
-
-        let child = Command::new(app.command.clone())
-            .args(app.args.clone())
-            .spawn()
-            .log()?;
-        app.process = Some(child);
-        app.status = ProcessStatus::Running;
+        Self::kill(app, id).await?;
+        Self::run_update(app, id).await?;
+        app.spawn().await?;
+        audit(AuditAction::Update, app);
         debug!("Succesfully Restarting Process {id}");
         Ok(())
     }
@@ -204,58 +1039,209 @@ impl AppRuntime {
     pub async fn start_all(&self) -> AppRuntimeResult<()> {
         let mut apps = self.apps.write().await;
         for (id, app) in apps.iter_mut() {
-            debug!("Starting Process {id}");
-            let child = Command::new(app.command.clone())
-                .args(app.args.clone())
-                .spawn()
-                .log()?;
-            app.process = Some(child);
-            app.status = ProcessStatus::Running;
+            Self::start(app, id).await?;
         }
         Ok(())
     }
 
-    pub async fn restart_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
-        let id = id.as_ref();
+    /// same as [`AppRuntime::start_all`] but spawn every process at once, e.g. many services
+    /// the order of [`AppProcess::ready_port`] wait is lost, use `start_all` when it matter.
+    /// spawned without holding the lock so the runtime can still be read meanwhile,
+    /// process removed or changed before its spawn finish is killed instead of stored.
+    /// failed one doesnt stop the others and the first error in runtime order is returned
+    pub async fn start_all_concurrent(&self) -> AppRuntimeResult<()> {
+        let specs: Vec<AppProcess> = {
+            let apps = self.apps.read().await;
+            apps.values().map(AppProcess::spawn_spec).collect()
+        };
+        let launches = specs.iter().map(|spec| async move {
+            debug!("Starting Process {}", spec.id);
+            spec.launch().await
+        });
+        let launched = join_all(launches).await;
+
         let mut apps = self.apps.write().await;
-        if let Some(app) = apps.get_mut(id) {
-            Self::restart(app, id).await
-        } else {
-            error!("Process {id} not found");
-            Err(AppError::NotFound(id.to_string()))
+        let mut res = Ok(());
+        for (spec, launched) in specs.iter().zip(launched) {
+            let id = &spec.id;
+            match (launched, apps.get_mut(id)) {
+                (Ok(process), Some(app)) if app.same_spec(spec) => {
+                    app.running(process);
+                    audit(AuditAction::Start, app);
+                }
+                (Ok(mut process), _) => {
+                    debug!("Process {id} changed while starting, killing it");
+                    process.kill().await.ok();
+                }
+                (Err(e), _) => {
+                    if res.is_ok() {
+                        res = Err(e);
+                    }
+                }
+            }
         }
+        res
     }
 
-    /// Using indexmap so the process start in order
-    pub async fn restart_all(&self) -> AppRuntimeResult<()> {
+    /// Diff the runtime against the desired process list by id
+    /// removed process will be stopped, new process will be started
+    /// and process whose [`AppProcess::same_spec`] changed will be restarted with the new spec
+    /// unchanged process keep running with the rest of its new spec applied,
+    /// restart count and captured output carry over either way.
+    /// failing process doesnt stop the others, they are all returned in [`AppError::Reconcile`]
+    pub async fn reconcile(&self, desired: Vec<AppProcess>) -> AppRuntimeResult<()> {
         let mut apps = self.apps.write().await;
-        for (id, app) in apps.iter_mut() {
-            Self::restart(app, id).await?;
+        let mut errors = vec![];
+
+        let removed: Vec<String> = apps
+            .keys()
+            .filter(|id| !desired.iter().any(|app| &app.id == *id))
+            .cloned()
+            .collect();
+        for id in removed {
+            if let Some(app) = apps.get_mut(&id) {
+                // kept when it cant be stopped
+                if let Err(e) = Self::stop(app, &id).await {
+                    errors.push((id, e));
+                    continue;
+                }
+                apps.shift_remove(&id);
+                debug!("Removed Process {id} from runtime");
+            }
         }
-        Ok(())
+
+        for mut app in desired {
+            let id = app.id.clone();
+            match apps.get_mut(&id) {
+                Some(current) if current.same_spec(&app) => {
+                    app.process = current.process.take();
+                    app.status = current.status.clone();
+                    app.started_at = current.started_at;
+                    app.inherit(current);
+                    self.attach(&mut app);
+                    *current = app;
+                }
+                Some(current) => {
+                    debug!("Process {id} changed, applying new spec");
+                    if let Err(e) = Self::stop(current, &id).await {
+                        errors.push((id, e));
+                        continue;
+                    }
+                    app.inherit(current);
+                    self.attach(&mut app);
+                    // the new spec is kept stopped when it fail to start
+                    if let Err(e) = Self::start(&mut app, &id).await {
+                        errors.push((id, e));
+                    }
+                    *current = app;
+                }
+                None => {
+                    self.attach(&mut app);
+                    if let Err(e) = Self::start(&mut app, &id).await {
+                        errors.push((id.clone(), e));
+                    }
+                    apps.insert(id, app);
+                }
+            }
+        }
+        Self::collect_errors(errors)
     }
-    pub async fn version_update_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
+
+    pub async fn restart_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
         let id = id.as_ref();
-        debug!("Restarting Process {id}");
         let mut apps = self.apps.write().await;
         if let Some(app) = apps.get_mut(id) {
-            Self::ver_update(app, id).await
+            Self::restart(app, id).await
         } else {
             error!("Process {id} not found");
             Err(AppError::NotFound(id.to_string()))
         }
     }
 
-    /// Using indexmap so the process start in order
-    pub async fn version_update_all(&self) -> AppRuntimeResult<()> {
-        let mut apps = self.apps.write().await;
-        for (id, app) in apps.iter_mut() {
-            Self::ver_update(app, id).await?;
-        }
-        Ok(())
+    /// id of the process directly listing `id` in their [`AppProcess::depends_on`]
+    pub async fn dependents_of(&self, id: impl AsRef<str>) -> Vec<String> {
+        deps::dependents(&*self.apps.read().await, id.as_ref())
     }
 
-    pub async fn stop_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
+    /// restart the process then its transitive dependents, each one after what it depends on
+    /// e.g. restarting database so the service using it reconnect.
+    /// fail with [`AppError::DependencyCycle`] before restarting anything
+    pub async fn restart_with_dependents(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        if !apps.contains_key(id) {
+            error!("Process {id} not found");
+            return Err(AppError::NotFound(id.to_string()));
+        }
+        let order = deps::restart_order(&apps, id)
+            .map_err(AppError::DependencyCycle)
+            .log()?;
+        for id in order {
+            if let Some(app) = apps.get_mut(&id) {
+                Self::restart(app, &id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Using indexmap so the process start in order
+    pub async fn restart_all(&self) -> AppRuntimeResult<()> {
+        let mut apps = self.apps.write().await;
+        for (id, app) in apps.iter_mut() {
+            Self::restart(app, id).await?;
+        }
+        Ok(())
+    }
+    /// restart only process that isnt running, e.g. recovering after dependency outage
+    /// every process is tried, returning result per id. restart here count as crash,
+    /// process whose [`RestartPolicy::max_retries`] is used up fail with [`AppError::RestartsExhausted`]
+    pub async fn restart_failed(&self) -> Vec<(String, AppRuntimeResult<()>)> {
+        let mut apps = self.apps.write().await;
+        let mut results = vec![];
+        for (id, app) in apps.iter_mut() {
+            Self::refresh_status(app);
+            if app.status == ProcessStatus::Running {
+                continue;
+            }
+            let restarts = app.crash_count;
+            if let Some(policy) = app.restart_policy.as_ref() {
+                if policy.exhausted(restarts) {
+                    let err = AppError::RestartsExhausted {
+                        id: id.clone(),
+                        restarts,
+                    };
+                    results.push((id.clone(), Err(err).log()));
+                    continue;
+                }
+            }
+            app.crash_count += 1;
+            results.push((id.clone(), Self::restart(app, id).await));
+        }
+        results
+    }
+
+    pub async fn version_update_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        debug!("Restarting Process {id}");
+        let mut apps = self.apps.write().await;
+        if let Some(app) = apps.get_mut(id) {
+            Self::ver_update(app, id).await
+        } else {
+            error!("Process {id} not found");
+            Err(AppError::NotFound(id.to_string()))
+        }
+    }
+
+    /// Using indexmap so the process start in order
+    pub async fn version_update_all(&self) -> AppRuntimeResult<()> {
+        let mut apps = self.apps.write().await;
+        for (id, app) in apps.iter_mut() {
+            Self::ver_update(app, id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn stop_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
         let id = id.as_ref();
         let mut apps = self.apps.write().await;
         if let Some(app) = apps.get_mut(id) {
@@ -266,14 +1252,176 @@ impl AppRuntime {
         }
     }
 
+    /// send SIGTERM and kill it only if its still running after `grace`
+    /// regardless of [`AppProcess::stop_grace`]
+    pub async fn stop_graceful(
+        &self,
+        id: impl AsRef<str>,
+        grace: Duration,
+    ) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        if let Some(app) = apps.get_mut(id) {
+            Self::stop_with(app, id, Some(grace)).await
+        } else {
+            error!("Process {id} not found");
+            Err(AppError::NotFound(id.to_string()))
+        }
+    }
+
+    /// remove the process from the runtime leaving it running, e.g. hand it off to init
+    /// its handle is released with [`ProcessHandle::detach`] so backend killing on drop doesnt stop it
+    /// return its pid, None when it wasnt running
+    pub async fn detach_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<Option<u32>> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        let Some(mut app) = apps.shift_remove(id) else {
+            error!("Process {id} not found");
+            return Err(AppError::NotFound(id.to_string()));
+        };
+        audit(AuditAction::Detach, &app);
+        let pid = match app.process.take() {
+            Some(process) if app.status == ProcessStatus::Running => process.detach(),
+            _ => None,
+        };
+        debug!("Detached Process {id} with pid {pid:?}");
+        Ok(pid)
+    }
+
+    /// deliver unix signal without stopping it, e.g. `libc::SIGHUP` to reload config
+    #[cfg(unix)]
+    pub async fn send_signal(&self, id: impl AsRef<str>, signal: i32) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        let Some(app) = apps.get_mut(id) else {
+            error!("Process {id} not found");
+            return Err(AppError::NotFound(id.to_string()));
+        };
+        match app
+            .process
+            .as_mut()
+            .filter(|_| app.status == ProcessStatus::Running)
+        {
+            Some(process) => {
+                process.signal(signal).log()?;
+                debug!("Sent signal {signal} to Process {id}");
+                Ok(())
+            }
+            None => Err(AppError::NotRunning(id.to_string())).log(),
+        }
+    }
+
+    /// stop the process then take it out of the runtime, e.g. retired job
+    /// its kept when stopping fail
+    pub async fn remove_process(&self, id: impl AsRef<str>) -> AppRuntimeResult<AppProcess> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        if let Some(app) = apps.get_mut(id) {
+            Self::stop(app, id).await?;
+        }
+        match apps.shift_remove(id) {
+            Some(app) => {
+                debug!("Removed Process {id} from runtime");
+                Ok(app)
+            }
+            None => {
+                error!("Process {id} not found");
+                Err(AppError::NotFound(id.to_string()))
+            }
+        }
+    }
+
+    /// stop every given process, one failing doesnt stop the rest
+    async fn stop_each<'a>(
+        apps: impl Iterator<Item = (&'a String, &'a mut AppProcess)>,
+    ) -> Vec<(String, AppError)> {
+        let mut errors = vec![];
+        for (id, app) in apps {
+            if let Err(e) = Self::stop(app, id).await {
+                errors.push((id.clone(), e));
+            }
+        }
+        errors
+    }
+
+    fn collect_errors(errors: Vec<(String, AppError)>) -> AppRuntimeResult<()> {
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(AppError::Reconcile(errors)).log(),
+        }
+    }
+
+    /// stop every process then take them all out of the runtime in order
+    /// process failing to stop is kept in the runtime and returned in [`AppError::Reconcile`]
+    pub async fn clear(&self) -> AppRuntimeResult<Vec<AppProcess>> {
+        let mut apps = self.apps.write().await;
+        let errors = Self::stop_each(apps.iter_mut()).await;
+        let (kept, removed) = apps
+            .drain(..)
+            .partition(|(id, _)| errors.iter().any(|(failed, _)| failed == id));
+        *apps = kept;
+        debug!("Removed every stopped process from runtime");
+        Self::collect_errors(errors)?;
+        Ok(removed.into_values().collect())
+    }
+
+    /// stop every process, the runtime should not be used after this
+    /// its marked shut down even when some process failed to stop
+    pub async fn shutdown(&self) -> AppRuntimeResult<()> {
+        let res = self.stop_all().await;
+        self.shut_down.store(true, Ordering::SeqCst);
+        debug!("AppRuntime shut down");
+        res
+    }
+
+    /// stop every process, failing one doesnt stop the rest
+    /// they are all returned in [`AppError::Reconcile`]
     pub async fn stop_all(&self) -> AppRuntimeResult<()> {
+        let mut apps = self.apps.write().await;
+        Self::collect_errors(Self::stop_each(apps.iter_mut()).await)
+    }
+
+    /// start every stopped process with the label
+    pub async fn start_group(&self, label: impl AsRef<str>) -> AppRuntimeResult<()> {
+        let label = label.as_ref();
         let mut apps = self.apps.write().await;
         for (id, app) in apps.iter_mut() {
-            Self::stop(app, id).await?;
+            if app.has_label(label) && app.status == ProcessStatus::Stopped {
+                Self::start(app, id).await?;
+            }
         }
         Ok(())
     }
 
+    /// stop every process with the label, same as [`AppRuntime::stop_all`] on failure
+    pub async fn stop_group(&self, label: impl AsRef<str>) -> AppRuntimeResult<()> {
+        let label = label.as_ref();
+        let mut apps = self.apps.write().await;
+        let group = apps.iter_mut().filter(|(_, app)| app.has_label(label));
+        Self::collect_errors(Self::stop_each(group).await)
+    }
+
+    /// how many times the process has been restarted, None if not found
+    pub async fn restart_count(&self, id: impl AsRef<str>) -> Option<u32> {
+        let apps = self.apps.read().await;
+        apps.get(id.as_ref()).map(|app| app.restart_count)
+    }
+
+    /// clear the restart and crash counter, e.g. after fixing flapping process
+    pub async fn reset_restart_count(&self, id: impl AsRef<str>) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        if let Some(app) = apps.get_mut(id) {
+            app.restart_count = 0;
+            app.crash_count = 0;
+            debug!("Reset restart count of Process {id}");
+            Ok(())
+        } else {
+            error!("Process {id} not found");
+            Err(AppError::NotFound(id.to_string()))
+        }
+    }
+
     pub async fn check_status(&self, id: impl AsRef<str>) -> AppRuntimeResult<ProcessStatus> {
         let id = id.as_ref();
         let apps = self.apps.read().await;
@@ -284,6 +1432,84 @@ impl AppRuntime {
         Err(AppError::NotFound(id.to_string()))
     }
 
+    /// os pid of the process to correlate with `ps` or monitoring, None when its not running
+    pub async fn get_pid(&self, id: impl AsRef<str>) -> AppRuntimeResult<Option<u32>> {
+        let id = id.as_ref();
+        let apps = self.apps.read().await;
+        if let Some(app) = apps.get(id) {
+            return Ok(app.pid());
+        }
+        error!("Process {id} not found");
+        Err(AppError::NotFound(id.to_string()))
+    }
+
+    /// how long the process has been running since its last spawn, None when its not running
+    /// see [`AppRuntime::list_info`] for every process at once
+    pub async fn uptime(&self, id: impl AsRef<str>) -> AppRuntimeResult<Option<Duration>> {
+        let id = id.as_ref();
+        let apps = self.apps.read().await;
+        if let Some(app) = apps.get(id) {
+            return Ok(app.uptime());
+        }
+        error!("Process {id} not found");
+        Err(AppError::NotFound(id.to_string()))
+    }
+
+    /// id and pid of every process
+    pub async fn list_pids(&self) -> Vec<(String, Option<u32>)> {
+        let apps = self.apps.read().await;
+        apps.iter()
+            .map(|(id, app)| (id.clone(), app.pid()))
+            .collect()
+    }
+
+    /// stream buffered output of the process then follow the new lines like `tail -f`
+    pub async fn tail(
+        &self,
+        id: impl AsRef<str>,
+        from: TailStart,
+    ) -> AppRuntimeResult<impl Stream<Item = String>> {
+        let id = id.as_ref();
+        let apps = self.apps.read().await;
+        match apps.get(id) {
+            Some(app) => match &app.output {
+                Some(output) => Ok(output.tail(from)),
+                None => Err(AppError::NoCapture(id.to_string())),
+            },
+            None => {
+                error!("Process {id} not found");
+                Err(AppError::NotFound(id.to_string()))
+            }
+        }
+    }
+
+    /// captured output of the last run that exited, e.g. to attach the tail to crash alert
+    /// its the current run when the process is stopped or the previous one once its restarted,
+    /// bounded by the capture capacity. fail with [`AppError::NoCapture`] without output capture
+    pub async fn last_output_on_exit(&self, id: impl AsRef<str>) -> AppRuntimeResult<Vec<String>> {
+        let id = id.as_ref();
+        let mut apps = self.apps.write().await;
+        let Some(app) = apps.get_mut(id) else {
+            error!("Process {id} not found");
+            return Err(AppError::NotFound(id.to_string()));
+        };
+        Self::refresh_status(app);
+        match &app.output {
+            Some(output) if app.status == ProcessStatus::Running => Ok(output.last_run()),
+            Some(output) => Ok(output.current_run()),
+            None => Err(AppError::NoCapture(id.to_string())),
+        }
+    }
+
+    /// only the new output line of the process from now on, for live log view,
+    /// fail with [`AppError::NoCapture`] unless its built with [`AppProcess::capture_output`]
+    pub async fn read_output(
+        &self,
+        id: impl AsRef<str>,
+    ) -> AppRuntimeResult<impl Stream<Item = String>> {
+        self.tail(id, TailStart::Last(0)).await
+    }
+
     /// List id and status
     pub async fn list_status(&self) -> Vec<(String, ProcessStatus)> {
         let mut con = vec![];
@@ -294,40 +1520,960 @@ impl AppRuntime {
         con
     }
 
+    /// List status with pid, uptime and restart count
+    /// the status is the cached one, see [`AppRuntime::refresh_and_list`]
+    pub async fn list_info(&self) -> Vec<ProcessInfo> {
+        let apps = self.apps.read().await;
+        apps.values().map(ProcessInfo::from).collect()
+    }
+
+    /// [`AppRuntime::update_status`] then [`AppRuntime::list_info`] under the same lock,
+    /// so exited process is never reported running, e.g. for dashboard
+    pub async fn refresh_and_list(&self) -> Vec<ProcessInfo> {
+        let mut apps = self.apps.write().await;
+        apps.values_mut()
+            .map(|app| {
+                Self::refresh_status(app);
+                ProcessInfo::from(&*app)
+            })
+            .collect()
+    }
+
+    /// [`AppRuntime::list_info`] as json array, e.g. for management cli
+    pub async fn list_status_json(&self) -> String {
+        serde_json::to_string(&self.list_info().await).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// graphviz graph of the process and their [`AppProcess::depends_on`] edge,
+    /// filled green when running and grey when stopped, render it with `dot -Tsvg`
+    pub async fn to_dot(&self) -> String {
+        let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+        let apps = self.apps.read().await;
+        let mut dot = String::from("digraph appruntime {\n    node [style=filled];\n");
+        for (id, app) in apps.iter() {
+            let color = match app.status {
+                ProcessStatus::Running => "palegreen",
+                ProcessStatus::Stopped => "lightgrey",
+            };
+            dot.push_str(&format!("    {} [fillcolor={color}];\n", quote(id)));
+        }
+        for (id, app) in apps.iter() {
+            for dep in &app.depends_on {
+                dot.push_str(&format!("    {} -> {};\n", quote(id), quote(dep)));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// List id and status of process with the label
+    pub async fn list_by_label(&self, label: impl AsRef<str>) -> Vec<(String, ProcessStatus)> {
+        let label = label.as_ref();
+        let apps = self.apps.read().await;
+        apps.iter()
+            .filter(|(_, app)| app.has_label(label))
+            .map(|(id, app)| (id.clone(), app.status.clone()))
+            .collect()
+    }
+
+    fn refresh_status(app: &mut AppProcess) {
+        if let Some(process) = &mut app.process {
+            if let Ok(status) = process.try_wait() {
+                match status {
+                    Some(_) => app.status = ProcessStatus::Stopped,
+                    None => app.status = ProcessStatus::Running,
+                }
+                debug!("Process {} status updated", app.id);
+            }
+        }
+    }
+
     pub async fn update_status(&self) {
         let mut apps = self.apps.write().await;
         for app in apps.values_mut() {
-            if let Some(process) = &mut app.process {
-                if let Ok(status) = process.try_wait() {
-                    match status {
-                        Some(_) => app.status = ProcessStatus::Stopped,
-                        None => app.status = ProcessStatus::Running,
+            Self::refresh_status(app);
+        }
+    }
+
+    /// poll the process every [`STATUS_POLL_INTERVAL`] until it reach `target`
+    /// e.g. start A, wait until its up, then start B
+    pub async fn wait_for_status(
+        &self,
+        id: impl AsRef<str>,
+        target: ProcessStatus,
+        timeout: Duration,
+    ) -> AppRuntimeResult<()> {
+        let id = id.as_ref();
+        let deadline = Instant::now() + timeout;
+        loop {
+            {
+                let mut apps = self.apps.write().await;
+                let Some(app) = apps.get_mut(id) else {
+                    error!("Process {id} not found");
+                    return Err(AppError::NotFound(id.to_string()));
+                };
+                Self::refresh_status(app);
+                if app.status == target {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(AppError::StatusTimeout(id.to_string(), target)).log();
+            }
+            sleep(STATUS_POLL_INTERVAL).await;
+        }
+    }
+
+    /// check every running process once, applying the restart policy of the exited one,
+    /// stopping the one over its max runtime and spawning the one whose backoff is over
+    pub async fn supervise_once(&self) -> AppRuntimeResult<()> {
+        let mut apps = self.apps.write().await;
+        for (id, app) in apps.iter_mut() {
+            if let Some(max) = app.max_runtime.filter(|max| {
+                app.status == ProcessStatus::Running
+                    && app.started_at.is_some_and(|at| at.elapsed() >= *max)
+            }) {
+                warn!("Process {id} ran longer than {max:?}, stopping it");
+                // already logged, try again on the next pass
+                let grace = app.stop_grace.unwrap_or(DEFAULT_STOP_GRACE);
+                if Self::stop_with(app, id, Some(grace)).await.is_ok() {
+                    app.stop_reason = Some(StopReason::Timeout);
+                }
+                continue;
+            }
+            if let Some(at) = app.restart_at {
+                if Instant::now() >= at {
+                    debug!("Backoff of Process {id} is over, restarting");
+                    Self::respawn(app, id).await;
+                }
+                continue;
+            }
+            if app.status != ProcessStatus::Running {
+                continue;
+            }
+            let Some(status) = app.process.as_mut().and_then(|p| p.try_wait().log().ok()?) else {
+                continue;
+            };
+            app.status = ProcessStatus::Stopped;
+            let action = app
+                .restart_policy
+                .as_ref()
+                .map_or(RestartAction::Leave, |policy| policy.action(status));
+            let policy = app.restart_policy.as_ref();
+            let uptime = app.started_at.map_or(Duration::ZERO, |at| at.elapsed());
+            if policy.is_some_and(|p| p.is_stable(uptime)) {
+                app.crash_count = 0;
+            }
+            match action {
+                RestartAction::Restart if policy.is_some_and(|p| p.exhausted(app.crash_count)) => {
+                    let restarts = app.crash_count;
+                    error!(
+                        "Process {id} exited with {status}, giving up after {restarts} restarts"
+                    );
+                }
+                RestartAction::Restart => {
+                    let delay = policy.map_or(Duration::ZERO, |p| p.delay(app.crash_count));
+                    if delay.is_zero() {
+                        debug!("Process {id} exited with {status}, restarting");
+                        Self::respawn(app, id).await;
+                    } else {
+                        warn!("Process {id} exited with {status}, restarting in {delay:?}");
+                        app.restart_at = Some(Instant::now() + delay);
                     }
-                    debug!("Process {} status updated", app.id);
                 }
+                RestartAction::Leave => debug!("Process {id} exited with {status}"),
+                RestartAction::GiveUp => error!("Process {id} exited with {status}, giving up"),
             }
         }
+        Ok(())
+    }
+
+    /// restart exited process for the supervisor, failing spawn count as restart
+    /// and is retried after the policy backoff until its exhausted so it doesnt stop the others
+    async fn respawn(app: &mut AppProcess, id: &str) {
+        app.restart_at = None;
+        let res = Self::start(app, id).await;
+        app.restart_count += 1;
+        app.crash_count += 1;
+        let Err(e) = res else {
+            return;
+        };
+        let policy = app.restart_policy.as_ref();
+        let restarts = app.crash_count;
+        if policy.is_some_and(|p| p.exhausted(restarts)) {
+            error!("Respawning Process {id} failed: {e}, giving up after {restarts} restarts");
+        } else {
+            let delay = policy.map_or(Duration::ZERO, |p| p.delay(restarts));
+            warn!("Respawning Process {id} failed: {e}, retrying in {delay:?}");
+            app.restart_at = Some(Instant::now() + delay);
+        }
+    }
+
+    /// run [`AppRuntime::supervise_once`] every `interval`, process failing to respawn is
+    /// retried on its own so it doesnt stop the supervision of the others
+    pub async fn supervise(&self, interval: Duration) -> AppRuntimeResult<()> {
+        loop {
+            self.supervise_once().await?;
+            sleep(interval).await;
+        }
     }
 
-    pub async fn wait_for_exit(&self) {
+    /// [`AppRuntime::supervise`] on background task, abort the handle to stop supervising
+    pub fn spawn_supervise(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> JoinHandle<AppRuntimeResult<()>> {
+        tokio::spawn(async move { self.supervise(interval).await })
+    }
+
+    /// wait every process to exit in order
+    /// return the [`aggregate_exit_code`] so the supervisor can exit with it
+    pub async fn wait_for_exit(&self) -> i32 {
         let mut apps = self.apps.write().await;
+        let mut statuses = vec![];
         for app in apps.values_mut() {
             if let Some(process) = &mut app.process {
-                process.wait().await.log().ok();
+                statuses.extend(process.wait().await.log().ok());
             }
             app.status = ProcessStatus::Stopped;
         }
+        aggregate_exit_code(&statuses)
+    }
+
+    /// same as [`AppRuntime::wait_for_exit`] but keep the exit code of every process,
+    /// None when killed by signal
+    pub async fn wait_for_exit_codes(&self) -> Vec<(String, Option<i32>)> {
+        let mut apps = self.apps.write().await;
+        let mut codes = vec![];
+        for (id, app) in apps.iter_mut() {
+            if let Some(process) = &mut app.process {
+                if let Ok(status) = process.wait().await.log() {
+                    codes.push((id.clone(), status.code()));
+                }
+            }
+            app.status = ProcessStatus::Stopped;
+        }
+        codes
+    }
+
+    /// wait until the first running process exit and return its id and exit code
+    /// e.g. restart the whole group once any of them die, None when nothing is running
+    pub async fn wait_for_any(&self) -> Option<(String, Option<i32>)> {
+        loop {
+            {
+                let mut apps = self.apps.write().await;
+                let mut running = false;
+                for (id, app) in apps.iter_mut() {
+                    let Some(process) = app
+                        .process
+                        .as_mut()
+                        .filter(|_| app.status == ProcessStatus::Running)
+                    else {
+                        continue;
+                    };
+                    running = true;
+                    if let Ok(Some(status)) = process.try_wait().log() {
+                        app.status = ProcessStatus::Stopped;
+                        return Some((id.clone(), status.code()));
+                    }
+                }
+                if !running {
+                    return None;
+                }
+            }
+            sleep(STATUS_POLL_INTERVAL).await;
+        }
     }
 }
 
-/// To stop the runtime and all it process when dropped
+impl ProcessManager for AppRuntime {
+    type Process = AppProcess;
+    type Status = ProcessStatus;
+    type Error = AppError;
+
+    async fn add(&self, app: AppProcess) {
+        self.add_process(app).await
+    }
+    async fn start_all(&self) -> AppRuntimeResult<()> {
+        self.start_all().await
+    }
+    async fn stop(&self, id: &str) -> AppRuntimeResult<()> {
+        self.stop_process(id).await
+    }
+    async fn restart(&self, id: &str) -> AppRuntimeResult<()> {
+        self.restart_process(id).await
+    }
+    async fn check_status(&self, id: &str) -> AppRuntimeResult<ProcessStatus> {
+        self.check_status(id).await
+    }
+    async fn list_status(&self) -> Vec<(String, ProcessStatus)> {
+        self.list_status().await
+    }
+}
+
+/// Graceful stop need async, so drop only warn when [`AppRuntime::shutdown`] wasnt called
+/// the children are still killed once their handle is dropped with the last runtime reference
 impl Drop for AppRuntime {
     fn drop(&mut self) {
-        let y = std::mem::take(self);
-        spawn_blocking(|| async move {
-            if y.stop_all().await.log().is_ok() {
-                debug!("Dropped the AppRuntime succesfully");
-            }
+        if self.shut_down.load(Ordering::SeqCst) {
+            return;
+        }
+        let running = self.apps.try_read().map_or(0, |apps| {
+            apps.values()
+                .filter(|app| app.status == ProcessStatus::Running)
+                .count()
+        });
+        if running > 0 {
+            warn!("AppRuntime dropped without shutdown, killing {running} process");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// handle that never exit and cant be killed
+    #[derive(Debug)]
+    struct StuckHandle;
+
+    #[async_trait::async_trait]
+    impl ProcessHandle for StuckHandle {
+        async fn kill(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::PermissionDenied.into())
+        }
+        fn try_wait(&mut self) -> std::io::Result<Option<std::process::ExitStatus>> {
+            Ok(None)
+        }
+        async fn wait(&mut self) -> std::io::Result<std::process::ExitStatus> {
+            std::future::pending().await
+        }
+    }
+
+    #[derive(Debug)]
+    struct StuckBackend;
+
+    #[async_trait::async_trait]
+    impl ProcessBackend for StuckBackend {
+        async fn spawn(&self, _: &AppProcess) -> AppRuntimeResult<Box<dyn ProcessHandle>> {
+            Ok(Box::new(StuckHandle))
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_past_failed_stop() {
+        let runtime = AppRuntime::default();
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        let stuck = AppProcess::new("stuck", "sh", vec![]).backend(StuckBackend);
+        runtime.add_process_then_run(stuck).await.unwrap();
+        runtime
+            .add_process_then_run(AppProcess::new("sleep", "sh", sleep))
+            .await
+            .unwrap();
+        let err = runtime.shutdown().await.unwrap_err();
+        assert!(matches!(&err, AppError::Reconcile(errors) if errors[0].0 == "stuck"));
+        assert_eq!(
+            runtime.list_status().await,
+            [
+                ("stuck".to_string(), ProcessStatus::Running),
+                ("sleep".to_string(), ProcessStatus::Stopped),
+            ]
+        );
+        assert!(runtime.shut_down.load(Ordering::SeqCst));
+
+        let cleared = runtime.clear().await;
+        assert!(matches!(cleared, Err(AppError::Reconcile(_))));
+        assert_eq!(runtime.list_status().await.len(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn drop_kill_children() {
+        let runtime = AppRuntime::default();
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        runtime
+            .add_process_then_run(AppProcess::new("sleep", "sh", sleep))
+            .await
+            .unwrap();
+        let pid = runtime.get_pid("sleep").await.unwrap().unwrap();
+        drop(runtime);
+
+        // reaped in background, zombie count as gone
+        let gone = || {
+            std::fs::read_to_string(format!("/proc/{pid}/stat"))
+                .map_or(true, |stat| stat.contains(") Z "))
+        };
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !gone() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(gone());
+    }
+
+    #[tokio::test]
+    async fn ready_only_once_listening() {
+        let runtime = AppRuntime::default();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        let app = AppProcess::new("up", "sh", sleep).ready_port(port);
+        runtime.add_process_then_run(app).await.unwrap();
+        drop(listener);
+
+        let exit = vec!["-c".to_string(), "exit 3".to_string()];
+        let app = AppProcess::new("down", "sh", exit).ready_port(port);
+        let err = runtime.add_process_then_run(app).await.unwrap_err();
+        assert!(matches!(err, AppError::ExitedBeforeReady { id, .. } if id == "down"));
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_start_keep_order() {
+        let runtime = AppRuntime::default();
+        for id in ["c", "a", "b"] {
+            let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+            runtime.add_process(AppProcess::new(id, "sh", sleep)).await;
+        }
+        runtime.start_all_concurrent().await.unwrap();
+        let statuses = runtime.list_status().await;
+        assert_eq!(
+            statuses,
+            ["c", "a", "b"].map(|id| (id.to_string(), ProcessStatus::Running))
+        );
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_start_dont_hold_lock() {
+        let runtime = AppRuntime::default();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        let slow = AppProcess::new("slow", "sh", sleep.clone())
+            .ready_port(port)
+            .timeouts(Duration::from_millis(300), DEFAULT_STOP_TIMEOUT);
+        runtime.add_process(slow).await;
+        runtime
+            .add_process(AppProcess::new("fast", "sh", sleep))
+            .await;
+
+        let (res, statuses) = tokio::join!(runtime.start_all_concurrent(), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            tokio::time::timeout(Duration::from_millis(100), runtime.list_status()).await
         });
+        assert!(statuses.is_ok(), "list blocked by concurrent start");
+        assert!(matches!(res, Err(AppError::StartTimeout { id }) if id == "slow"));
+        assert_eq!(
+            runtime.list_status().await,
+            [
+                ("slow".to_string(), ProcessStatus::Stopped),
+                ("fast".to_string(), ProcessStatus::Running),
+            ]
+        );
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refreshed_list_see_exited_process() {
+        let runtime = AppRuntime::default();
+        let app = AppProcess::new("once", "true", vec![]);
+        runtime.add_process_then_run(app).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(runtime.list_info().await[0].status, ProcessStatus::Running);
+        let info = runtime.refresh_and_list().await;
+        assert_eq!(
+            (info[0].status.clone(), info[0].pid),
+            (ProcessStatus::Stopped, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn reconcile_keep_state_and_continue_on_error() {
+        let runtime = AppRuntime::default();
+        let echo = |word: &str| vec!["-c".to_string(), format!("echo {word}; sleep 5")];
+        let app = AppProcess::new("a", "sh", echo("one")).capture_output(10);
+        runtime.add_process_then_run(app).await.unwrap();
+        runtime.restart_process("a").await.unwrap();
+        let pid = runtime.get_pid("a").await.unwrap();
+
+        // same spec, only the policy change
+        let app = AppProcess::new("a", "sh", echo("one"))
+            .capture_output(10)
+            .restart_policy(RestartPolicy::always());
+        runtime.reconcile(vec![app]).await.unwrap();
+        assert_eq!(runtime.get_pid("a").await.unwrap(), pid);
+        {
+            let apps = runtime.apps.read().await;
+            assert!(apps["a"].restart_policy.is_some());
+            assert_eq!(apps["a"].restart_count, 1);
+        }
+
+        let desired = vec![
+            AppProcess::new("a", "sh", echo("two")).capture_output(10),
+            AppProcess::new("bad", "appruntime-missing-executable", vec![]),
+            AppProcess::new("c", "sh", echo("three")),
+        ];
+        let err = runtime.reconcile(desired).await.unwrap_err();
+        assert!(
+            matches!(&err, AppError::Reconcile(errors) if errors.len() == 1 && errors[0].0 == "bad")
+        );
+        assert_eq!(
+            runtime.list_status().await,
+            [
+                ("a".to_string(), ProcessStatus::Running),
+                ("bad".to_string(), ProcessStatus::Stopped),
+                ("c".to_string(), ProcessStatus::Running),
+            ]
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        {
+            let apps = runtime.apps.read().await;
+            assert_eq!(apps["a"].restart_count, 1);
+            let lines = apps["a"].output.as_ref().unwrap().lines();
+            assert_eq!(lines.first().map(String::as_str), Some("one"));
+            assert_eq!(lines.last().map(String::as_str), Some("two"));
+        }
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn retry_budget_count_consecutive_crash() {
+        let runtime = AppRuntime::default();
+        let exit = || vec!["-c".to_string(), "exit 1".to_string()];
+        let policy = RestartPolicy::always().max_retries(1);
+        let flaky = AppProcess::new("flaky", "sh", exit())
+            .restart_policy(policy.clone())
+            .update_command(vec!["true".to_string()]);
+        let stable = AppProcess::new("stable", "sh", exit())
+            .restart_policy(policy.reset_after(Duration::ZERO));
+        runtime.add_process_then_run(flaky).await.unwrap();
+        runtime.add_process_then_run(stable).await.unwrap();
+        // manual restart doesnt use the budget up
+        runtime.restart_process("flaky").await.unwrap();
+        for _ in 0..2 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            runtime.supervise_once().await.unwrap();
+        }
+        let apps = runtime.apps.read().await;
+        let counts = |id: &str| (apps[id].restart_count, apps[id].crash_count);
+        assert_eq!(counts("flaky"), (2, 1));
+        assert_eq!(counts("stable"), (2, 1));
+        // flaky gave up on its second crash, stable keep restarting
+        assert_eq!(apps["flaky"].status, ProcessStatus::Stopped);
+        assert_eq!(apps["stable"].status, ProcessStatus::Running);
+    }
+
+    #[tokio::test]
+    async fn supervise_past_failed_respawn() {
+        let runtime = Arc::new(AppRuntime::default());
+        runtime.set_context("code", "1");
+        let exit = vec!["-c".to_string(), "exit {code}".to_string()];
+        let policy = RestartPolicy::always().max_retries(3);
+        let broken = AppProcess::new("broken", "sh", exit)
+            .template_args()
+            .restart_policy(policy);
+        let healthy = AppProcess::new(
+            "healthy",
+            "sh",
+            vec!["-c".to_string(), "exit 1".to_string()],
+        )
+        .restart_policy(RestartPolicy::always());
+        runtime.add_process_then_run(broken).await.unwrap();
+        runtime.add_process_then_run(healthy).await.unwrap();
+        // args of the next spawn cant be rendered anymore
+        runtime.remove_context("code");
+
+        let supervisor = runtime.clone().spawn_supervise(Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!supervisor.is_finished());
+        {
+            let apps = runtime.apps.read().await;
+            assert_eq!(apps["broken"].restart_count, 3);
+            assert_eq!(apps["broken"].status, ProcessStatus::Stopped);
+            assert!(apps["healthy"].restart_count > 3);
+        }
+        supervisor.abort();
+    }
+
+    #[tokio::test]
+    async fn restart_failed_respect_max_retries() {
+        let runtime = AppRuntime::default();
+        let exit = vec!["-c".to_string(), "exit 0".to_string()];
+        let limited = AppProcess::new("limited", "sh", exit.clone())
+            .restart_policy(RestartPolicy::always().max_retries(1));
+        runtime.add_process_then_run(limited).await.unwrap();
+        runtime
+            .add_process_then_run(AppProcess::new("free", "sh", exit))
+            .await
+            .unwrap();
+
+        for round in 0..2 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let results = runtime.restart_failed().await;
+            assert_eq!(results.len(), 2);
+            assert!(results[1].1.is_ok());
+            match round {
+                0 => assert!(results[0].1.is_ok()),
+                _ => assert!(matches!(
+                    results[0].1,
+                    Err(AppError::RestartsExhausted { restarts: 1, .. })
+                )),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn remove_stop_and_evict() {
+        let runtime = AppRuntime::default();
+        for id in ["a", "b", "c"] {
+            let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+            runtime
+                .add_process_then_run(AppProcess::new(id, "sh", sleep))
+                .await
+                .unwrap();
+        }
+        assert!(runtime.uptime("b").await.unwrap().is_some());
+        let removed = runtime.remove_process("b").await.unwrap();
+        assert_eq!(removed.uptime(), None);
+        assert_eq!(removed.status, ProcessStatus::Stopped);
+        assert!(runtime.remove_process("b").await.is_err());
+
+        let cleared = runtime.clear().await.unwrap();
+        assert_eq!(
+            cleared
+                .iter()
+                .map(|app| app.id.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+        assert!(runtime.list_status().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_signaled_by_mode() {
+        let runtime = AppRuntime::default();
+        let script = r#"case "$APP_MODE" in update) exit 0;; *) exec sleep 5;; esac"#;
+        let args = vec!["-c".to_string(), script.to_string()];
+        let app = AppProcess::new("env", "sh", args).update_env("APP_MODE", "update");
+        runtime.add_process_then_run(app).await.unwrap();
+        let update = runtime.version_update_process("env");
+        tokio::time::timeout(Duration::from_secs(2), update)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        let app = AppProcess::new("cmd", "sh", sleep).update_command(vec!["false".to_string()]);
+        runtime.add_process_then_run(app).await.unwrap();
+        let err = runtime.version_update_process("cmd").await.unwrap_err();
+        assert!(matches!(err, AppError::Update(id, _) if id == "cmd"));
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn exit_codes_of_every_process() {
+        let runtime = AppRuntime::default();
+        for (id, script) in [("slow", "sleep 5"), ("fast", "exit 3")] {
+            let args = vec!["-c".to_string(), script.to_string()];
+            runtime
+                .add_process_then_run(AppProcess::new(id, "sh", args))
+                .await
+                .unwrap();
+        }
+        let first = runtime.wait_for_any().await;
+        assert_eq!(first, Some(("fast".to_string(), Some(3))));
+
+        runtime.stop_process("slow").await.unwrap();
+        let codes = runtime.wait_for_exit_codes().await;
+        assert_eq!(
+            codes,
+            [("slow".to_string(), None), ("fast".to_string(), Some(3))]
+        );
+        assert_eq!(runtime.wait_for_any().await, None);
+    }
+
+    #[tokio::test]
+    async fn healthcheck_catch_crash_on_start() {
+        let runtime = AppRuntime::default();
+        let settle = Duration::from_millis(200);
+        let crash = vec!["-c".to_string(), "exit 2".to_string()];
+        let err = runtime
+            .start_with_healthcheck(AppProcess::new("crash", "sh", crash), settle)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::CrashedOnStart { id, .. } if id == "crash"));
+
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        runtime
+            .start_with_healthcheck(AppProcess::new("up", "sh", sleep), settle)
+            .await
+            .unwrap();
+        let ids: Vec<_> = runtime
+            .list_status()
+            .await
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, ["up"]);
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stop_process_over_max_runtime() {
+        let runtime = AppRuntime::default();
+        let marker = std::env::temp_dir().join(format!("appruntime-max-{}", std::process::id()));
+        let script = format!(
+            "trap 'touch {}; exit 0' TERM; while true; do sleep 0.05; done",
+            marker.display()
+        );
+        let app = AppProcess::new("batch", "sh", vec!["-c".to_string(), script])
+            .max_runtime(Duration::from_millis(100))
+            .restart_policy(RestartPolicy::always());
+        runtime.add_process_then_run(app).await.unwrap();
+        runtime.supervise_once().await.unwrap();
+        assert_eq!(runtime.list_info().await[0].status, ProcessStatus::Running);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        runtime.supervise_once().await.unwrap();
+        let info = runtime.list_info().await;
+        assert_eq!(info[0].status, ProcessStatus::Stopped);
+        assert_eq!(info[0].stop_reason, Some(StopReason::Timeout));
+        // got SIGTERM first even without stop grace
+        assert!(marker.exists());
+        std::fs::remove_file(&marker).ok();
+    }
+
+    #[tokio::test]
+    async fn signal_without_stopping() {
+        let runtime = AppRuntime::default();
+        let script = "trap 'echo reload' HUP; while true; do sleep 0.05; done";
+        let args = vec!["-c".to_string(), script.to_string()];
+        let app = AppProcess::new("hup", "sh", args).capture_output(10);
+        runtime.add_process_then_run(app).await.unwrap();
+        let mut lines = Box::pin(runtime.read_output("hup").await.unwrap());
+        // give sh time to install the trap
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        runtime.send_signal("hup", libc::SIGHUP).await.unwrap();
+        assert_eq!(lines.next().await.unwrap(), "reload");
+        assert_eq!(
+            runtime.check_status("hup").await.unwrap(),
+            ProcessStatus::Running
+        );
+
+        runtime.stop_process("hup").await.unwrap();
+        let err = runtime.send_signal("hup", libc::SIGHUP).await.unwrap_err();
+        assert!(matches!(err, AppError::NotRunning(_)));
+        assert!(runtime.send_signal("missing", libc::SIGHUP).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn shutdown_stop_every_process() {
+        let runtime = AppRuntime::default();
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        runtime
+            .add_process_then_run(AppProcess::new("sleep", "sh", sleep))
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(1);
+        runtime
+            .wait_for_status("sleep", ProcessStatus::Running, timeout)
+            .await
+            .unwrap();
+        runtime.shutdown().await.unwrap();
+        runtime
+            .wait_for_status("sleep", ProcessStatus::Stopped, timeout)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn output_stream_tag_lines() {
+        let runtime = AppRuntime::default();
+        let mut stream = Box::pin(runtime.output_stream());
+        for id in ["a", "b"] {
+            let echo = vec!["-c".to_string(), format!("echo {id}")];
+            let app = AppProcess::new(id, "sh", echo).capture_output(10);
+            runtime.add_process_then_run(app).await.unwrap();
+        }
+        let mut lines = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+        lines.sort();
+        let tagged = |id: &str| (id.to_string(), id.to_string());
+        assert_eq!(lines, [tagged("a"), tagged("b")]);
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[cfg(feature = "pty")]
+    #[tokio::test]
+    async fn pty_is_a_terminal() {
+        let runtime = AppRuntime::default();
+        let script = vec!["-c".to_string(), "test -t 1 && echo tty".to_string()];
+        let app = AppProcess::new("pty", "sh", script)
+            .capture_output(10)
+            .with_pty();
+        runtime.add_process_then_run(app).await.unwrap();
+        let mut tail = Box::pin(runtime.tail("pty", TailStart::Beginning).await.unwrap());
+        let line = tokio::time::timeout(Duration::from_secs(5), tail.next()).await;
+        assert_eq!(line.unwrap().as_deref(), Some("tty"));
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn templated_args_from_context() {
+        let runtime = AppRuntime::default();
+        runtime.set_context("dir", "/etc/fleet");
+        let mut stream = Box::pin(runtime.output_stream());
+        let args = vec!["{dir}/{id}.toml".to_string()];
+        let app = AppProcess::new("w1", "echo", args)
+            .template_args()
+            .capture_output(10);
+        runtime.add_process_then_run(app).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().1, "/etc/fleet/w1.toml");
+        runtime.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn detached_process_survive_shutdown() {
+        let runtime = AppRuntime::default();
+        let sleep = vec!["-c".to_string(), "sleep 5".to_string()];
+        runtime
+            .add_process_then_run(AppProcess::new("daemon", "sh", sleep))
+            .await
+            .unwrap();
+        let pid = runtime.get_pid("daemon").await.unwrap();
+        assert_eq!(runtime.list_pids().await, [("daemon".to_string(), pid)]);
+        assert!(matches!(
+            runtime.get_pid("missing").await,
+            Err(AppError::NotFound(_))
+        ));
+        let pid = runtime.detach_process("daemon").await.unwrap().unwrap();
+        runtime.shutdown().await.unwrap();
+
+        let alive = |signal: &str| {
+            std::process::Command::new("kill")
+                .args([signal, &pid.to_string()])
+                .status()
+                .unwrap()
+                .success()
+        };
+        assert!(alive("-0"));
+        assert!(alive("-9"));
+        // reaped once its gone, zombie would still answer signal 0
+        for _ in 0..50 {
+            if !alive("-0") {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("detached process {pid} wasnt reaped");
+    }
+
+    #[tokio::test]
+    async fn graceful_stop_let_process_clean_up() {
+        let runtime = AppRuntime::default();
+        let marker = std::env::temp_dir().join(format!("appruntime-term-{}", std::process::id()));
+        let script = format!(
+            "trap 'touch {}; exit 0' TERM; while true; do sleep 0.05; done",
+            marker.display()
+        );
+        let app = AppProcess::new("db", "sh", vec!["-c".to_string(), script]);
+        runtime.add_process_then_run(app).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        runtime
+            .stop_graceful("db", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(marker.exists());
+        std::fs::remove_file(&marker).ok();
+    }
+
+    #[tokio::test]
+    async fn missing_executable() {
+        let runtime = AppRuntime::default();
+        let app = AppProcess::new("typo", "definitely-not-installed-cmd", vec![]);
+        let err = runtime.add_process_then_run(app).await.unwrap_err();
+        assert!(
+            matches!(err, AppError::ExecutableNotFound { command } if command == "definitely-not-installed-cmd")
+        );
+    }
+
+    #[tokio::test]
+    async fn dot_graph_with_dependency() {
+        let runtime = AppRuntime::default();
+        runtime
+            .add_process(AppProcess::new("db", "true", vec![]))
+            .await;
+        let web = AppProcess::new("web", "true", vec![]).depends_on("db");
+        runtime.add_process(web).await;
+        let dot = r#"digraph appruntime {
+    node [style=filled];
+    "db" [fillcolor=lightgrey];
+    "web" [fillcolor=lightgrey];
+    "web" -> "db";
+}
+"#;
+        assert_eq!(runtime.to_dot().await, dot);
+    }
+
+    #[test]
+    fn only_transient_spawn_error_retried() {
+        let os = |code| AppError::SubProcess(std::io::Error::from_raw_os_error(code));
+        assert!(os(libc::EAGAIN).is_transient() && os(libc::ETXTBSY).is_transient());
+        assert!(!os(libc::ENOENT).is_transient() && !os(libc::EACCES).is_transient());
+        let command = "missing".to_string();
+        assert!(!AppError::ExecutableNotFound { command }.is_transient());
+    }
+
+    #[test]
+    fn spawn_setting_change_spec() {
+        let app = || AppProcess::new("web", "sh", vec!["serve.sh".to_string()]);
+        let kept = app()
+            .label("web")
+            .restart_policy(RestartPolicy::always())
+            .backend(LocalBackend);
+        assert!(app().same_spec(&kept));
+        let changed = [
+            app().backend(StuckBackend),
+            app().capture_output(10),
+            app().ready_port(8080),
+            app().timeouts(Duration::from_secs(1), DEFAULT_STOP_TIMEOUT),
+            app().spawn_retries(2),
+            app().update_command(vec!["migrate".to_string()]),
+        ];
+        assert!(changed.iter().all(|changed| !app().same_spec(changed)));
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let app = AppProcess::new("web", "sh", vec!["serve.sh".to_string()])
+            .label("web")
+            .capture_output_with_limits(10, 100)
+            .restart_policy(RestartPolicy::on_failure());
+        let def = AppProcessDef::from(&app);
+        let json = serde_json::to_string(&def).unwrap();
+        let def: AppProcessDef = serde_json::from_str(&json).unwrap();
+        let back = AppProcess::from(def.clone());
+        assert!(back.same_spec(&app));
+        assert_eq!(back.labels, app.labels);
+        assert_eq!(def.capture_output, Some((10, 100)));
+    }
+
+    #[test]
+    fn process_info_json() {
+        let info = ProcessInfo {
+            id: "web".to_string(),
+            status: ProcessStatus::Running,
+            pid: Some(42),
+            uptime_secs: Some(7),
+            restart_count: 1,
+            stop_reason: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&info).unwrap(),
+            r#"{"id":"web","status":"running","pid":42,"uptime_secs":7,"restart_count":1}"#
+        );
     }
 }
@@ -0,0 +1,342 @@
+use std::{
+    collections::VecDeque,
+    io::IsTerminal,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Child,
+    sync::broadcast,
+    time::timeout,
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+/// where [`AppRuntime::tail`](crate::AppRuntime::tail) start from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TailStart {
+    /// every line still on the buffer
+    Beginning,
+    /// only the last n lines
+    Last(usize),
+}
+
+/// lines kept for subscriber of [`AppRuntime::output_stream`](crate::AppRuntime::output_stream) lagging behind
+pub const OUTPUT_STREAM_CAPACITY: usize = 1024;
+
+/// log target of line forwarded with [`AppProcess::log_output`](crate::AppProcess::log_output)
+pub const OUTPUT_TARGET: &str = "appruntime::output";
+
+/// line tagged with the process printing it
+#[derive(Debug, Clone)]
+pub(crate) struct TaggedLine {
+    pub(crate) id: String,
+    pub(crate) prefix: String,
+    pub(crate) line: String,
+}
+
+/// where the line of the process go beside its own buffer, set when its added to the runtime
+#[derive(Debug)]
+pub(crate) struct Forward {
+    pub(crate) id: String,
+    pub(crate) prefix: String,
+    /// also log it with [`OUTPUT_TARGET`]
+    pub(crate) log: bool,
+    pub(crate) tx: broadcast::Sender<TaggedLine>,
+}
+
+/// `prefix | line` like docker compose, with `color` the prefix get ansi color picked from it
+/// so the same process keep the same color
+pub fn prefix_line(prefix: &str, line: &str, color: bool) -> String {
+    if !color {
+        return format!("{prefix} | {line}");
+    }
+    let hash = prefix
+        .bytes()
+        .fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+    // red to cyan, skipping black and white
+    let code = 31 + hash % 6;
+    format!("\x1b[{code}m{prefix} |\x1b[0m {line}")
+}
+
+/// default total bytes kept by [`OutputBuffer::new`]
+pub const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+
+/// line without trailing newline is pushed once the process print nothing for this long
+const PARTIAL_LINE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// lossy decode the line without its line ending, invalid utf8 become `U+FFFD`
+fn take_line(line: &mut Vec<u8>) -> String {
+    let end = line.strip_suffix(b"\n").unwrap_or(line);
+    let end = end.strip_suffix(b"\r").unwrap_or(end);
+    let text = String::from_utf8_lossy(end).into_owned();
+    line.clear();
+    text
+}
+
+#[derive(Debug, Default)]
+struct Buffered {
+    lines: VecDeque<String>,
+    bytes: usize,
+    dropped: u64,
+    /// total lines ever pushed and the count when the current run started
+    pushed: u64,
+    run_start: u64,
+    /// lines of the run before the current one still buffered when it ended
+    last_run: Vec<String>,
+}
+
+impl Buffered {
+    fn current_run(&self) -> Vec<String> {
+        let len = (self.pushed - self.run_start).min(self.lines.len() as u64) as usize;
+        self.lines
+            .iter()
+            .skip(self.lines.len() - len)
+            .cloned()
+            .collect()
+    }
+}
+
+/// ring buffer of captured stdout/stderr lines with live subscription
+/// bounded by both line count and total bytes, the oldest lines are dropped first
+#[derive(Debug)]
+pub struct OutputBuffer {
+    lines: Mutex<Buffered>,
+    capacity: usize,
+    max_bytes: usize,
+    tx: broadcast::Sender<String>,
+    forward: Mutex<Option<Forward>>,
+}
+
+impl OutputBuffer {
+    /// keep at most `capacity` lines and [`DEFAULT_MAX_BYTES`]
+    pub fn new(capacity: usize) -> Self {
+        Self::with_limits(capacity, DEFAULT_MAX_BYTES)
+    }
+
+    pub fn with_limits(capacity: usize, max_bytes: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self {
+            lines: Mutex::new(Buffered::default()),
+            capacity,
+            max_bytes,
+            tx,
+            forward: Mutex::new(None),
+        }
+    }
+
+    /// also send every line into runtime wide channel and the logger
+    pub(crate) fn forward(&self, forward: Forward) {
+        *self.forward.lock().unwrap() = Some(forward);
+    }
+
+    pub fn push(&self, mut line: String) {
+        if line.len() > self.max_bytes {
+            let mut cut = self.max_bytes;
+            while !line.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            line.truncate(cut);
+        }
+        let mut buf = self.lines.lock().unwrap();
+        buf.bytes += line.len();
+        buf.pushed += 1;
+        buf.lines.push_back(line.clone());
+        while buf.lines.len() > self.capacity || buf.bytes > self.max_bytes {
+            let Some(old) = buf.lines.pop_front() else {
+                break;
+            };
+            buf.bytes -= old.len();
+            buf.dropped += 1;
+        }
+        drop(buf);
+        if let Some(forward) = &*self.forward.lock().unwrap() {
+            if forward.log {
+                let color = std::io::stdout().is_terminal();
+                log::info!(target: OUTPUT_TARGET, "{}", prefix_line(&forward.prefix, &line, color));
+            }
+            let _ = forward.tx.send(TaggedLine {
+                id: forward.id.clone(),
+                prefix: forward.prefix.clone(),
+                line: line.clone(),
+            });
+        }
+        // no subscriber is fine
+        let _ = self.tx.send(line);
+    }
+
+    /// max lines kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// max total bytes kept
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// snapshot of the buffered lines
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().lines.iter().cloned().collect()
+    }
+
+    /// lines printed since the process was last spawned, still there after it exit
+    pub fn current_run(&self) -> Vec<String> {
+        self.lines.lock().unwrap().current_run()
+    }
+
+    /// lines printed by the run before the current one, e.g. the crash output after its restarted
+    pub fn last_run(&self) -> Vec<String> {
+        self.lines.lock().unwrap().last_run.clone()
+    }
+
+    /// mark the start of new run, keeping the lines of the previous one
+    pub(crate) fn start_run(&self) {
+        let mut buf = self.lines.lock().unwrap();
+        buf.last_run = buf.current_run();
+        buf.run_start = buf.pushed;
+    }
+
+    /// how many lines has been dropped to respect the limits
+    pub fn dropped(&self) -> u64 {
+        self.lines.lock().unwrap().dropped
+    }
+
+    /// buffered lines followed by live one, dropping the stream unsubscribe it
+    /// lines missed because the subscriber is lagging are skipped
+    pub fn tail(&self, from: TailStart) -> impl Stream<Item = String> {
+        // subscribe while holding the lock so no line is duplicated or lost between both
+        let buf = self.lines.lock().unwrap();
+        let lines = &buf.lines;
+        let rx = self.tx.subscribe();
+        let skip = match from {
+            TailStart::Beginning => 0,
+            TailStart::Last(n) => lines.len().saturating_sub(n),
+        };
+        let buffered: Vec<String> = lines.iter().skip(skip).cloned().collect();
+        drop(buf);
+
+        tokio_stream::iter(buffered).chain(BroadcastStream::new(rx).filter_map(|l| l.ok()))
+    }
+
+    /// take the child stdout/stderr and feed every line into the buffer
+    pub(crate) fn capture(self: &Arc<Self>, child: &mut Child) {
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(Self::read_lines(self.clone(), stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(Self::read_lines(self.clone(), stderr));
+        }
+    }
+
+    /// feed blocking reader into the same line pipeline, e.g. pty master
+    #[cfg(feature = "pty")]
+    pub(crate) fn capture_reader(self: &Arc<Self>, mut reader: Box<dyn std::io::Read + Send>) {
+        use tokio::io::AsyncWriteExt;
+
+        let (mut tx, rx) = tokio::io::duplex(8 * 1024);
+        tokio::spawn(Self::read_lines(self.clone(), rx));
+        let handle = tokio::runtime::Handle::current();
+        std::thread::spawn(move || {
+            let mut buf = [0; 4096];
+            // pty read fail with EIO once the child exit, treat it as eof
+            while let Ok(n @ 1..) = reader.read(&mut buf) {
+                if handle.block_on(tx.write_all(&buf[..n])).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    async fn read_lines(buffer: Arc<Self>, reader: impl AsyncRead + Unpin) {
+        let mut reader = BufReader::new(reader);
+        let mut line = vec![];
+        loop {
+            // read_until keep the partial bytes in `line` when timed out
+            match timeout(PARTIAL_LINE_TIMEOUT, reader.read_until(b'\n', &mut line)).await {
+                Ok(Ok(0)) | Ok(Err(_)) => break,
+                Ok(Ok(_)) if !line.ends_with(b"\n") => {}
+                Ok(Ok(_)) => buffer.push(take_line(&mut line)),
+                Err(_) if !line.is_empty() => buffer.push(take_line(&mut line)),
+                Err(_) => {}
+            }
+        }
+        if !line.is_empty() {
+            buffer.push(take_line(&mut line));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tail_buffered_then_live() {
+        let buffer = OutputBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(i.to_string());
+        }
+        assert_eq!(buffer.lines(), ["2", "3", "4"]);
+        assert_eq!(buffer.dropped(), 2);
+
+        let mut stream = Box::pin(buffer.tail(TailStart::Last(1)));
+        buffer.push("5".to_string());
+        assert_eq!(stream.next().await.as_deref(), Some("4"));
+        assert_eq!(stream.next().await.as_deref(), Some("5"));
+    }
+
+    #[tokio::test]
+    async fn lossy_and_partial_lines() {
+        use tokio::io::AsyncWriteExt;
+
+        let buffer = Arc::new(OutputBuffer::new(10));
+        let (mut tx, rx) = tokio::io::duplex(64);
+        let reader = tokio::spawn(OutputBuffer::read_lines(buffer.clone(), rx));
+        tx.write_all(b"a\xffb\r\nprompt> ").await.unwrap();
+        tokio::time::sleep(PARTIAL_LINE_TIMEOUT * 2).await;
+        assert_eq!(buffer.lines(), ["a\u{fffd}b", "prompt> "]);
+
+        tx.write_all(b"tail").await.unwrap();
+        drop(tx);
+        reader.await.unwrap();
+        assert_eq!(buffer.lines(), ["a\u{fffd}b", "prompt> ", "tail"]);
+    }
+
+    #[test]
+    fn prefix_keep_color_per_process() {
+        assert_eq!(prefix_line("web", "ready", false), "web | ready");
+        let colored = prefix_line("web", "ready", true);
+        assert!(colored.starts_with("\x1b[3") && colored.ends_with("|\x1b[0m ready"));
+        assert_eq!(colored, prefix_line("web", "ready", true));
+    }
+
+    #[test]
+    fn keep_output_of_previous_run() {
+        let buffer = OutputBuffer::new(3);
+        buffer.push("boot".to_string());
+        buffer.start_run();
+        for line in ["a", "b", "c", "panic"] {
+            buffer.push(line.to_string());
+        }
+        assert_eq!(buffer.current_run(), ["b", "c", "panic"]);
+
+        buffer.start_run();
+        buffer.push("again".to_string());
+        assert_eq!(buffer.current_run(), ["again"]);
+        assert_eq!(buffer.last_run(), ["b", "c", "panic"]);
+    }
+
+    #[test]
+    fn bounded_by_bytes() {
+        let buffer = OutputBuffer::with_limits(10, 8);
+        buffer.push("aaaa".to_string());
+        buffer.push("bbbb".to_string());
+        buffer.push("cc".to_string());
+        assert_eq!(buffer.lines(), ["bbbb", "cc"]);
+        buffer.push("x".repeat(20));
+        assert_eq!(buffer.lines(), ["x".repeat(8)]);
+        assert_eq!(buffer.dropped(), 3);
+    }
+}
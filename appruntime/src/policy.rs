@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, process::ExitStatus, time::Duration};
+
+/// what [`AppRuntime::supervise`](crate::AppRuntime::supervise) do with exited process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RestartAction {
+    /// spawn it again
+    Restart,
+    /// leave it stopped, e.g. clean exit
+    Leave,
+    /// leave it stopped and log error, e.g. fatal config
+    GiveUp,
+}
+
+/// map exit code of the process into [`RestartAction`]
+/// modeled on systemd `Restart=`, specific code win over the success/failure action
+/// process killed by signal count as failure
+/// restart is immediate and unlimited unless [`RestartPolicy::max_retries`]/[`RestartPolicy::backoff`] set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    codes: HashMap<i32, RestartAction>,
+    on_success: RestartAction,
+    on_failure: RestartAction,
+    #[serde(default)]
+    max_retries: Option<u32>,
+    #[serde(default)]
+    backoff: Duration,
+    #[serde(default = "default_reset_after")]
+    reset_after: Duration,
+}
+
+/// uptime after which the crash count of the process start again, see [`RestartPolicy::reset_after`]
+pub const DEFAULT_RESET_AFTER: Duration = Duration::from_secs(60);
+
+fn default_reset_after() -> Duration {
+    DEFAULT_RESET_AFTER
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::on_failure()
+    }
+}
+
+impl RestartPolicy {
+    /// restart on nonzero exit, leave it on clean exit
+    pub fn on_failure() -> Self {
+        Self {
+            codes: HashMap::new(),
+            on_success: RestartAction::Leave,
+            on_failure: RestartAction::Restart,
+            max_retries: None,
+            backoff: Duration::ZERO,
+            reset_after: DEFAULT_RESET_AFTER,
+        }
+    }
+
+    /// restart whatever the exit code is
+    pub fn always() -> Self {
+        Self {
+            codes: HashMap::new(),
+            on_success: RestartAction::Restart,
+            on_failure: RestartAction::Restart,
+            max_retries: None,
+            backoff: Duration::ZERO,
+            reset_after: DEFAULT_RESET_AFTER,
+        }
+    }
+
+    /// never restart
+    pub fn never() -> Self {
+        Self {
+            codes: HashMap::new(),
+            on_success: RestartAction::Leave,
+            on_failure: RestartAction::Leave,
+            max_retries: None,
+            backoff: Duration::ZERO,
+            reset_after: DEFAULT_RESET_AFTER,
+        }
+    }
+
+    /// give up after `max` consecutive crash, counted by [`AppProcess::crash_count`](crate::AppProcess::crash_count)
+    /// so manual restart doesnt use it up and process running past [`RestartPolicy::reset_after`]
+    /// start again, or reset it with [`AppRuntime::reset_restart_count`](crate::AppRuntime::reset_restart_count)
+    pub fn max_retries(mut self, max: u32) -> Self {
+        self.max_retries = Some(max);
+        self
+    }
+
+    /// wait `backoff` before the first restart, doubled on every next one
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// crash of process that ran at least this long isnt consecutive anymore,
+    /// default to [`DEFAULT_RESET_AFTER`], better be longer than the backoff ceiling
+    pub fn reset_after(mut self, uptime: Duration) -> Self {
+        self.reset_after = uptime;
+        self
+    }
+
+    /// whether process running for `uptime` before exiting start its crash count again
+    pub fn is_stable(&self, uptime: Duration) -> bool {
+        uptime >= self.reset_after
+    }
+
+    /// delay before restarting the process already restarted `restarts` times
+    pub fn delay(&self, restarts: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(restarts.min(31)))
+    }
+
+    /// whether the process restarted `restarts` times hit the ceiling
+    pub fn exhausted(&self, restarts: u32) -> bool {
+        self.max_retries.is_some_and(|max| restarts >= max)
+    }
+
+    /// override the action of specific exit code
+    pub fn code(mut self, code: i32, action: RestartAction) -> Self {
+        self.codes.insert(code, action);
+        self
+    }
+
+    pub fn action(&self, status: ExitStatus) -> RestartAction {
+        match status.code().and_then(|code| self.codes.get(&code)) {
+            Some(action) => *action,
+            None if status.success() => self.on_success,
+            None => self.on_failure,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    #[test]
+    fn backoff_doubled_until_ceiling() {
+        let policy = RestartPolicy::always()
+            .max_retries(3)
+            .backoff(Duration::from_millis(100));
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert!(!policy.exhausted(2) && policy.exhausted(3));
+        assert_eq!(RestartPolicy::on_failure().delay(5), Duration::ZERO);
+    }
+
+    #[test]
+    fn action_by_exit_code() {
+        let exit = |code: i32| ExitStatus::from_raw(code << 8);
+        let policy = RestartPolicy::on_failure().code(78, RestartAction::GiveUp);
+        assert_eq!(policy.action(exit(0)), RestartAction::Leave);
+        assert_eq!(policy.action(exit(1)), RestartAction::Restart);
+        assert_eq!(policy.action(exit(78)), RestartAction::GiveUp);
+        // killed by SIGKILL
+        assert_eq!(
+            policy.action(ExitStatus::from_raw(9)),
+            RestartAction::Restart
+        );
+    }
+}
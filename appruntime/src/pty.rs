@@ -0,0 +1,119 @@
+use std::{fmt::Debug, io, process::ExitStatus, sync::Mutex};
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::{
+    backend::{ProcessBackend, ProcessHandle},
+    AppError, AppProcess, AppRuntimeResult, STATUS_POLL_INTERVAL,
+};
+
+/// spawn the child attached to pseudo terminal so `isatty` is true for it,
+/// set with [`AppProcess::with_pty`]
+/// stdout and stderr both come out of the pty master, so they are captured as one stream
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PtyBackend;
+
+/// child running on the pty, the master is kept so the terminal isnt hung up
+pub struct PtyHandle {
+    child: Box<dyn Child + Send + Sync>,
+    _master: Mutex<Box<dyn MasterPty + Send>>,
+}
+
+impl Debug for PtyHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PtyHandle")
+            .field("child", &self.child)
+            .finish_non_exhaustive()
+    }
+}
+
+fn pty_err(e: impl ToString) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+fn exit_status(status: portable_pty::ExitStatus) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(((status.exit_code() & 0xff) << 8) as i32)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(status.exit_code())
+    }
+}
+
+#[async_trait::async_trait]
+impl ProcessBackend for PtyBackend {
+    async fn spawn(&self, app: &AppProcess) -> AppRuntimeResult<Box<dyn ProcessHandle>> {
+        let program = which::which(&app.command).map_err(|_| AppError::ExecutableNotFound {
+            command: app.command.clone(),
+        })?;
+        let pair = native_pty_system()
+            .openpty(PtySize::default())
+            .map_err(pty_err)?;
+        let mut command = CommandBuilder::new(program);
+        command.args(app.rendered_args()?);
+        for (key, value) in &app.env {
+            command.env(key, value);
+        }
+        if let Some(cwd) = app.cwd.clone().or_else(|| std::env::current_dir().ok()) {
+            command.cwd(cwd);
+        }
+        let child = pair.slave.spawn_command(command).map_err(pty_err)?;
+        // the child hold its own copy, ours would keep the master open after it exit
+        drop(pair.slave);
+
+        let reader = pair.master.try_clone_reader().map_err(pty_err)?;
+        match &app.output {
+            Some(output) => output.capture_reader(reader),
+            // the child block once the pty buffer is full, so drain it anyway
+            None => {
+                let mut reader = reader;
+                std::thread::spawn(move || io::copy(&mut reader, &mut io::sink()));
+            }
+        }
+        Ok(Box::new(PtyHandle {
+            child,
+            _master: Mutex::new(pair.master),
+        }))
+    }
+}
+
+/// same as the local child, dropping the handle kill the process
+impl Drop for PtyHandle {
+    fn drop(&mut self) {
+        if matches!(self.child.try_wait(), Ok(None)) {
+            self.child.kill().ok();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ProcessHandle for PtyHandle {
+    async fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()?;
+        self.wait().await.map(|_| ())
+    }
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.child.try_wait()?.map(exit_status))
+    }
+    async fn wait(&mut self) -> io::Result<ExitStatus> {
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(status);
+            }
+            tokio::time::sleep(STATUS_POLL_INTERVAL).await;
+        }
+    }
+    fn id(&self) -> Option<u32> {
+        self.child.process_id()
+    }
+    /// the master is kept until the child exit so its terminal isnt hung up, then its reaped
+    fn detach(mut self: Box<Self>) -> Option<u32> {
+        let pid = self.id();
+        std::thread::spawn(move || self.child.wait());
+        pid
+    }
+}
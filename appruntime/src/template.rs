@@ -0,0 +1,80 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// values of the args placeholder shared by every process of the runtime,
+/// set with [`AppRuntime::set_context`](crate::AppRuntime::set_context)
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext(Arc<RwLock<BTreeMap<String, String>>>);
+
+impl TemplateContext {
+    pub(crate) fn set(&self, key: String, value: String) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, value);
+    }
+
+    pub(crate) fn remove(&self, key: &str) -> Option<String> {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+}
+
+/// replace every `{name}` with its value from `lookup`, `{{` and `}}` are literal brace
+/// fail on unknown name, unclosed placeholder or lone `}`
+pub fn render(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => out.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => out.push('}'),
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed placeholder {{{name}")),
+                    }
+                }
+                match lookup(&name) {
+                    Some(value) => out.push_str(&value),
+                    None => return Err(format!("unknown placeholder {{{name}}}")),
+                }
+            }
+            '}' => return Err("lone '}', escape it as '}}'".to_string()),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitute_and_escape() {
+        let lookup = |name: &str| (name == "dir").then(|| "/etc/app".to_string());
+        assert_eq!(
+            render("--config={dir}/{{x}}.toml", lookup).unwrap(),
+            "--config=/etc/app/{x}.toml"
+        );
+        assert!(render("{missing}", lookup).is_err());
+        assert!(render("{dir", lookup).is_err());
+        assert!(render("dir}", lookup).is_err());
+    }
+}
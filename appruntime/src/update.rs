@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{AppError, AppProcess, AppRuntimeResult};
+
+/// appended to the args of the update step when the process has no update mode
+pub const DEFAULT_UPDATE_ARGS: &[&str] = &["--update"];
+
+/// how the update step of [`AppRuntime::version_update_process`](crate::AppRuntime::version_update_process)
+/// tell the process to update itself, one per process so the last one set win.
+/// every mode run with the process env and cwd, unset is `Args(["--update"])`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateMode {
+    /// run the process with these appended to its args, e.g. `["migrate"]`
+    Args(Vec<String>),
+    /// run the process with its usual args and this env var on top of its env,
+    /// e.g. `APP_MODE=update`
+    Env(String, String),
+    /// run separate program and its args instead of the process
+    Command(Vec<String>),
+}
+
+impl UpdateMode {
+    /// update step command of the process, `mode` None is the default args
+    pub(crate) fn command(mode: Option<&Self>, app: &AppProcess) -> AppRuntimeResult<Command> {
+        let mut command = match mode {
+            Some(UpdateMode::Command(cmd)) => {
+                let Some((program, args)) = cmd.split_first() else {
+                    return Err(AppError::InvalidConfig {
+                        id: app.id.clone(),
+                        reason: "update command is empty".to_string(),
+                    });
+                };
+                let mut command = Command::new(program);
+                command.args(args);
+                command
+            }
+            _ => {
+                let mut command = Command::new(&app.command);
+                command.args(app.rendered_args()?);
+                command
+            }
+        };
+        command.envs(&app.env);
+        if let Some(cwd) = &app.cwd {
+            command.current_dir(cwd);
+        }
+        match mode {
+            None => command.args(DEFAULT_UPDATE_ARGS),
+            Some(UpdateMode::Args(args)) => command.args(args),
+            Some(UpdateMode::Env(key, value)) => command.env(key, value),
+            Some(UpdateMode::Command(_)) => &mut command,
+        };
+        Ok(command)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// first wait after the webhook fail
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// wait never grow past this
+const MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// snapshot of the webhook backoff, see [`Mylogger::webhook_backoff`](crate::Mylogger::webhook_backoff)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackoffState {
+    /// consecutive failed send, reset on success
+    pub failures: u32,
+    /// how long until send is tried again, `None` when not backing off
+    pub retry_in: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/// exponential backoff of the discord sink so dead endpoint isnt hammered on every log
+#[derive(Debug, Default)]
+pub(crate) struct Backoff {
+    state: Mutex<State>,
+}
+
+impl Backoff {
+    /// whether sending is allowed now
+    pub(crate) fn ready(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .retry_at
+            .is_none_or(|at| Instant::now() >= at)
+    }
+
+    pub(crate) fn success(&self) {
+        *self.state.lock().unwrap() = State::default();
+    }
+
+    /// double the wait on every consecutive failure up to [`MAX_DELAY`]
+    pub(crate) fn failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let delay = BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(state.failures))
+            .min(MAX_DELAY);
+        state.failures = state.failures.saturating_add(1);
+        state.retry_at = Some(Instant::now() + delay);
+    }
+
+    pub(crate) fn state(&self) -> BackoffState {
+        let state = self.state.lock().unwrap();
+        BackoffState {
+            failures: state.failures,
+            retry_in: state
+                .retry_at
+                .map(|at| at.saturating_duration_since(Instant::now()))
+                .filter(|d| !d.is_zero()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_then_reset() {
+        let backoff = Backoff::default();
+        assert!(backoff.ready());
+        backoff.failure();
+        backoff.failure();
+        assert!(!backoff.ready());
+        let state = backoff.state();
+        assert_eq!(state.failures, 2);
+        assert!(state.retry_in.unwrap() > BASE_DELAY);
+
+        backoff.success();
+        assert!(backoff.ready());
+        assert_eq!(backoff.state().retry_in, None);
+    }
+}
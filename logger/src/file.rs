@@ -0,0 +1,180 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::rotation::{open, roll, RotationPolicy};
+
+/// durability of the log file, set with [`Mylogger::set_flush_policy`](crate::Mylogger::set_flush_policy)
+/// line at `immediate` level or more severe is synced to disk right away,
+/// the rest is buffered and flushed every `interval`, zero interval flush after every line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    pub interval: Duration,
+    pub immediate: log::LevelFilter,
+}
+
+impl Default for FlushPolicy {
+    /// warn and error hit the disk immediately, the rest every second
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            immediate: log::LevelFilter::Warn,
+        }
+    }
+}
+
+enum Message {
+    Line(log::Level, String),
+    Policy(FlushPolicy),
+    Rotation(Option<RotationPolicy>),
+    Flush(mpsc::Sender<()>),
+}
+
+/// file sink writing on its own thread so the log call only pay for a channel send
+/// the thread flush and exit once the writer is dropped
+pub(crate) struct FileWriter {
+    tx: mpsc::Sender<Message>,
+}
+
+impl FileWriter {
+    pub(crate) fn spawn(
+        path: PathBuf,
+        file: File,
+        policy: FlushPolicy,
+        rotation: Option<RotationPolicy>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut written = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let mut file = BufWriter::new(file);
+            let mut policy = policy;
+            let mut rotation = rotation;
+            let mut dirty = false;
+            let mut last_flush = Instant::now();
+            loop {
+                // zero interval has nothing to wait for, block instead of spinning
+                let received = match policy.interval.saturating_sub(last_flush.elapsed()) {
+                    _ if policy.interval.is_zero() => {
+                        rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                    }
+                    wait => rx.recv_timeout(wait),
+                };
+                match received {
+                    Ok(Message::Line(level, line)) => {
+                        writeln!(file, "{line}").ok();
+                        written += line.len() as u64 + 1;
+                        if let Some(rotation) = rotation.filter(|r| written >= r.max_bytes) {
+                            sync(&mut file);
+                            // retried after another max_bytes when it fail
+                            written = 0;
+                            match roll(&path, &rotation) {
+                                Ok(new) => file = BufWriter::new(new),
+                                Err(e) => {
+                                    eprintln!("Failed to rotate {path:?}: {e}");
+                                    // the old handle may point at the rolled segment by now
+                                    if let Ok(new) = open(&path) {
+                                        file = BufWriter::new(new);
+                                    }
+                                }
+                            }
+                        }
+                        if level <= policy.immediate {
+                            sync(&mut file);
+                            dirty = false;
+                            last_flush = Instant::now();
+                            continue;
+                        }
+                        dirty = true;
+                    }
+                    Ok(Message::Policy(new)) => policy = new,
+                    Ok(Message::Rotation(new)) => rotation = new,
+                    Ok(Message::Flush(ack)) => {
+                        sync(&mut file);
+                        dirty = false;
+                        ack.send(()).ok();
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                // checked after every message too so steady traffic cant postpone it forever
+                if last_flush.elapsed() >= policy.interval {
+                    if dirty {
+                        file.flush().ok();
+                        dirty = false;
+                    }
+                    last_flush = Instant::now();
+                }
+            }
+            sync(&mut file);
+        });
+        Self { tx }
+    }
+
+    pub(crate) fn write(&self, level: log::Level, line: String) {
+        self.tx.send(Message::Line(level, line)).ok();
+    }
+
+    pub(crate) fn set_policy(&self, policy: FlushPolicy) {
+        self.tx.send(Message::Policy(policy)).ok();
+    }
+
+    pub(crate) fn set_rotation(&self, rotation: Option<RotationPolicy>) {
+        self.tx.send(Message::Rotation(rotation)).ok();
+    }
+
+    /// wait up to `timeout` for every line sent before to be synced
+    pub(crate) fn flush(&self, timeout: Duration) {
+        let (ack, done) = mpsc::channel();
+        if self.tx.send(Message::Flush(ack)).is_ok() {
+            done.recv_timeout(timeout).ok();
+        }
+    }
+}
+
+fn sync(file: &mut BufWriter<File>) {
+    file.flush().ok();
+    file.get_ref().sync_data().ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffered_until_interval_or_severe() {
+        let path = std::env::temp_dir().join(format!("logger-flush-{}.log", std::process::id()));
+        let file = File::create(&path).unwrap();
+        let policy = FlushPolicy {
+            interval: Duration::from_secs(60),
+            immediate: log::LevelFilter::Warn,
+        };
+        let writer = FileWriter::spawn(path.clone(), file, policy, None);
+        let read = || std::fs::read_to_string(&path).unwrap();
+
+        writer.write(log::Level::Info, "info".to_string());
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(read(), "");
+
+        writer.write(log::Level::Error, "error".to_string());
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(read(), "info\nerror\n");
+
+        writer.write(log::Level::Debug, "debug".to_string());
+        writer.flush(Duration::from_secs(1));
+        assert_eq!(read(), "info\nerror\ndebug\n");
+
+        writer.set_policy(FlushPolicy {
+            interval: Duration::ZERO,
+            ..policy
+        });
+        writer.write(log::Level::Info, "every line".to_string());
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(read(), "info\nerror\ndebug\nevery line\n");
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -1,41 +1,108 @@
 #![allow(unused)]
+//! ## `disabled` feature
+//! turn on `log`'s `max_level_off` and `release_max_level_off` so every `log::*!` macro is
+//! compiled into nothing and [`Mylogger`] become no-op.
+//! `log` features are global to the build, so this affect every crate using `log`,
+//! and it win over any other `max_level_*` feature enabled elsewhere.
+//! to only strip the verbose level use `log`'s own features instead, e.g.
+//! `log = { version = "0.4", features = ["release_max_level_info"] }`,
+//! runtime [`Mylogger::set_level`] can never go above the static max level.
 
 use std::{
-    fs::{File, OpenOptions},
+    fs::OpenOptions,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
 };
 
+use appname::exe_name;
 use chrono::Local;
 pub use log;
 use std::io::Write;
 
+mod backoff;
+mod file;
+mod rotation;
+mod sampling;
+mod sink;
+use backoff::Backoff;
+pub use backoff::BackoffState;
+use file::FileWriter;
+pub use file::FlushPolicy;
+pub use rotation::{Compression, RotationPolicy};
+pub use sampling::SampleRate;
+use sampling::Sampler;
+use sink::Sink;
+pub use sink::SinkId;
+
+/// part of the logger that can be changed after [`Mylogger::init`]
+struct Config {
+    webhook_url: Option<String>,
+    tag: Option<String>,
+    file: Option<FileWriter>,
+    flush_policy: FlushPolicy,
+    rotation: Option<RotationPolicy>,
+    exception: Vec<String>,
+    /// custom writer added with [`Mylogger::add_sink`], cloned out so they are written
+    /// without holding the config lock, their writer may log too
+    sinks: Vec<(SinkId, Arc<Sink>)>,
+    next_sink: u64,
+}
+
+fn sinks(config: &Config) -> Vec<Arc<Sink>> {
+    config.sinks.iter().map(|(_, sink)| sink.clone()).collect()
+}
+
 /// Logger for displaying log, can use file to write log there
 /// can use webhook to print error and wrning into discord
+/// `log` only accept one logger, so keep a clone before [`Mylogger::init`] to reconfigure it
+/// while running, the config is shared between clones
 #[derive(Clone)]
 pub struct Mylogger {
-    webhook_url: Option<String>,
-    tag: Option<String>,
     path: String,
-    file: Option<Arc<Mutex<File>>>,
-    exception: Vec<String>,
+    config: Arc<RwLock<Config>>,
+    /// max level as `LevelFilter as u8`, shared between clones
+    level: Arc<AtomicU8>,
+    samplers: Vec<Arc<Sampler>>,
+    /// webhook send still in flight
+    pending: Arc<AtomicUsize>,
+    /// webhook failure tracking, shared between clones
+    backoff: Arc<Backoff>,
 }
 
 impl Default for Mylogger {
+    /// stdout only, its log file name default to `<executable name>.log`
+    /// e.g. `myapp.log` for `target/release/myapp`, this crate name when it cant be resolved
     fn default() -> Self {
-        let name = format!("{}.log", env!("CARGO_PKG_NAME"));
+        let name = format!("{}.log", exe_name(env!("CARGO_PKG_NAME")));
         Self {
-            webhook_url: None,
-            tag: None,
             path: name,
-            file: None,
-            exception: vec![
-                "tokio".to_string(),
-                "reqwest".to_string(),
-                "hyper_util".to_string(),
-                "hyper".to_string(),
-                "tracing".to_string(),
-            ],
+            config: Arc::new(RwLock::new(Config {
+                webhook_url: None,
+                tag: None,
+                file: None,
+                flush_policy: FlushPolicy::default(),
+                rotation: None,
+                exception: vec![
+                    "tokio".to_string(),
+                    "reqwest".to_string(),
+                    "hyper_util".to_string(),
+                    "hyper".to_string(),
+                    "tracing".to_string(),
+                ],
+                sinks: vec![],
+                next_sink: 0,
+            })),
+            level: Arc::new(AtomicU8::new(if cfg!(debug_assertions) {
+                log::LevelFilter::Debug as u8
+            } else {
+                log::LevelFilter::Info as u8
+            })),
+            samplers: vec![],
+            pending: Arc::new(AtomicUsize::new(0)),
+            backoff: Arc::new(Backoff::default()),
         }
     }
 }
@@ -48,94 +115,310 @@ fn timest(ts: i64) -> String {
     format!("<t:{ts}:f>")
 }
 
+/// how long flush wait for in flight webhook send
+const FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// max content length of single discord message
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+/// split message into chunk of at most `limit` chars, preferring to cut on newline
+fn chunk_message(message: &str, limit: usize) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut rest = message;
+    while rest.chars().count() > limit {
+        let end = rest
+            .char_indices()
+            .nth(limit)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        let cut = match rest[..end].rfind('\n') {
+            Some(i) if i > 0 => i + 1,
+            _ => end,
+        };
+        chunks.push(rest[..cut].to_string());
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest.to_string());
+    }
+    chunks
+}
+
+/// render structured key values of the record as ` k=v` pairs
+struct KvWriter(String);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KvWriter {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        use std::fmt::Write;
+        write!(self.0, " {key}={value}").map_err(|_| log::kv::Error::msg("failed to write kv"))
+    }
+}
+
+fn key_values(record: &log::Record) -> String {
+    let mut kv = KvWriter(String::new());
+    record.key_values().visit(&mut kv).ok();
+    kv.0
+}
+
 impl Mylogger {
-    #[cfg(feature = "discord")]
+    /// send error and warning into discord webhook, mentioning `tag`
+    /// only work with `discord` feature, otherwise [`Mylogger::init`] warn that its ignored
     pub fn webhook_url(url: impl ToString, tag: impl ToString) -> Self {
-        Self {
-            webhook_url: Some(url.to_string()),
-            tag: Some(tag.to_string()),
-            ..Default::default()
-        }
+        let logger = Self::default();
+        logger.set_webhook(url, tag);
+        logger
     }
 
-    pub fn add_exception(mut self, ex: impl ToString) -> Self {
-        self.exception.push(ex.to_string());
+    /// change the discord webhook while running, see [`Mylogger::webhook_url`]
+    pub fn set_webhook(&self, url: impl ToString, tag: impl ToString) {
+        let mut config = self.config.write().unwrap();
+        config.webhook_url = Some(url.to_string());
+        config.tag = Some(tag.to_string());
+    }
+
+    /// stop sending into discord webhook
+    pub fn remove_webhook(&self) {
+        let mut config = self.config.write().unwrap();
+        config.webhook_url = None;
+        config.tag = None;
+    }
+
+    pub fn add_exception(self, ex: impl ToString) -> Self {
+        self.config.write().unwrap().exception.push(ex.to_string());
+        self
+    }
+
+    /// sample debug/trace record of target matching `target` so hot path doesnt flood the log
+    /// the number of dropped record is reported periodically
+    pub fn add_sampling(mut self, target: impl ToString, rate: SampleRate) -> Self {
+        self.samplers.push(Arc::new(Sampler::new(target, rate)));
+        self
+    }
+
+    /// write every formatted line passing `level` into the writer too, e.g. syslog socket
+    /// record still go through the logger level first, so the sink can only be stricter
+    /// can be called after [`Mylogger::init`], e.g. file discovered from runtime config
+    pub fn add_sink(&self, writer: impl Write + Send + 'static, level: log::LevelFilter) -> SinkId {
+        let mut config = self.config.write().unwrap();
+        let id = SinkId(config.next_sink);
+        config.next_sink += 1;
+        config.sinks.push((id, Arc::new(Sink::new(writer, level))));
+        id
+    }
+
+    /// same as [`Mylogger::add_sink`] for chaining while building the logger
+    pub fn with_sink(self, writer: impl Write + Send + 'static, level: log::LevelFilter) -> Self {
+        self.add_sink(writer, level);
         self
     }
 
+    /// flush and drop the sink, false if its already removed
+    pub fn remove_sink(&self, id: SinkId) -> bool {
+        let mut config = self.config.write().unwrap();
+        let Some(i) = config.sinks.iter().position(|(sink, _)| *sink == id) else {
+            return false;
+        };
+        let (_, sink) = config.sinks.remove(i);
+        drop(config);
+        sink.flush();
+        true
+    }
+
     pub fn with_file(path: impl AsRef<Path>) -> Self {
         let path = path.as_ref();
-        let file = Some(
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(path)
-                .ok()
-                .map(Mutex::new)
-                .map(Arc::new)
-                .expect("cant open file"),
-        );
         Self {
             path: path.to_string_lossy().to_string(),
-            file,
             ..Default::default()
         }
+        .set_file_logger(path)
     }
 
-    pub fn set_file_logger(mut self, path: impl AsRef<Path>) -> Self {
-        let file = Some(
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(path)
-                .ok()
-                .map(Mutex::new)
-                .map(Arc::new)
-                .expect("cant open file"),
-        );
-        self.file = file;
+    /// the file is written on its own thread following [`Mylogger::set_flush_policy`]
+    pub fn set_file_logger(self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .expect("cant open file");
+        let mut config = self.config.write().unwrap();
+        let writer = FileWriter::spawn(path.into(), file, config.flush_policy, config.rotation);
+        config.file = Some(writer);
+        drop(config);
         self
     }
+
+    /// roll the log file by size into `app.log.1`, `app.log.2`... optionally compressed,
+    /// None keep appending to single file. can be called after [`Mylogger::init`]
+    pub fn set_rotation(&self, rotation: Option<RotationPolicy>) {
+        let mut config = self.config.write().unwrap();
+        config.rotation = rotation;
+        if let Some(file) = &config.file {
+            file.set_rotation(rotation);
+        }
+    }
+
+    /// trade durability of the log file against write cost, see [`FlushPolicy`]
+    /// can be called after [`Mylogger::init`]
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        let mut config = self.config.write().unwrap();
+        config.flush_policy = policy;
+        if let Some(file) = &config.file {
+            file.set_policy(policy);
+        }
+    }
     pub fn init(self) {
-        #[cfg(debug_assertions)]
-        std::env::set_var("ALLOWED_PRINT_DEBUG", "1");
+        if std::env::var("ALLOWED_PRINT_DEBUG").is_ok_and(|x| x == "1")
+            && self.level() < log::LevelFilter::Debug
+        {
+            self.set_level(log::LevelFilter::Debug);
+        }
+        let level = self.level();
+        let ignored_webhook =
+            cfg!(not(feature = "discord")) && self.config.read().unwrap().webhook_url.is_some();
         log::set_boxed_logger(Box::new(self))
-            .map(|()| log::set_max_level(log::LevelFilter::Debug))
+            .map(|()| log::set_max_level(level))
             .ok();
+        if ignored_webhook {
+            log::warn!("Discord webhook is configured but `discord` feature is disabled, no alert will be sent");
+        }
+    }
+
+    /// stop accepting new record and flush the installed logger
+    /// call it on the end of `Appflow::cleanup` so the last lines are persisted before exit
+    pub fn shutdown() {
+        log::set_max_level(log::LevelFilter::Off);
+        log::logger().flush();
     }
 
+    /// current max level, default to debug on debug build and info on release
+    pub fn level(&self) -> log::LevelFilter {
+        let level = self.level.load(Ordering::Relaxed) as usize;
+        log::LevelFilter::iter()
+            .nth(level)
+            .unwrap_or(log::LevelFilter::Info)
+    }
+
+    /// change verbosity while running, e.g. debug burst during incident
+    /// the level is atomic and shared between clones, so keep a clone before [`Mylogger::init`]
+    /// and call this from any thread
+    pub fn set_level(&self, level: log::LevelFilter) {
+        self.level.store(level as u8, Ordering::Relaxed);
+        log::set_max_level(level);
+    }
+
+    /// failure/backoff state of the discord webhook, for diagnostic
+    pub fn webhook_backoff(&self) -> BackoffState {
+        self.backoff.state()
+    }
+
+    /// message is skipped while the webhook is backing off after failure
     #[cfg(feature = "discord")]
     pub async fn send_message(&self, message: &str) {
-        use reqwest::Client;
-        use serde_json::json;
+        use reqwest::{Client, StatusCode};
+        use serde_json::{json, Value};
+        use std::time::Duration;
 
         let client = Client::new();
-        if let Some(url) = &self.webhook_url {
-            client
-                .post(url)
-                .json(&json!({ "content": message }))
-                .send()
-                .await
-                .ok();
+        let url = self.config.read().unwrap().webhook_url.clone();
+        if let Some(url) = &url {
+            if !self.backoff.ready() {
+                return;
+            }
+            // chunk are sent in order, waiting out the rate limit before the next one
+            // still limited after the retries the rest is dropped so the message has no gap
+            for chunk in chunk_message(message, DISCORD_MESSAGE_LIMIT) {
+                let mut sent = false;
+                for _ in 0..3 {
+                    let res = match client
+                        .post(url)
+                        .json(&json!({ "content": chunk }))
+                        .send()
+                        .await
+                    {
+                        Ok(res) => res,
+                        Err(_) => {
+                            self.backoff.failure();
+                            return;
+                        }
+                    };
+                    match res.status() {
+                        status if status.is_success() => {
+                            self.backoff.success();
+                            sent = true;
+                            break;
+                        }
+                        StatusCode::TOO_MANY_REQUESTS => {}
+                        // server error, or deleted/unauthorized webhook answering 404/401
+                        _ => {
+                            self.backoff.failure();
+                            return;
+                        }
+                    }
+                    let retry_after = res
+                        .json::<Value>()
+                        .await
+                        .ok()
+                        .and_then(|v| v["retry_after"].as_f64())
+                        .unwrap_or(1.0);
+                    tokio::time::sleep(Duration::from_secs_f64(retry_after)).await;
+                }
+                if !sent {
+                    self.backoff.failure();
+                    return;
+                }
+            }
         }
     }
 }
 
 impl log::Log for Mylogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        if !self.exception.iter().any(|p| metadata.target().contains(p)) {
-            if let Ok(x) = std::env::var("ALLOWED_PRINT_DEBUG") {
-                if x == "1" {
-                    return metadata.level() <= log::Level::Debug;
-                }
-            }
-            return metadata.level() <= log::Level::Info;
+        if cfg!(feature = "disabled") {
+            return false;
+        }
+        let config = self.config.read().unwrap();
+        if !config
+            .exception
+            .iter()
+            .any(|p| metadata.target().contains(p))
+        {
+            return metadata.level() <= self.level();
         }
         false
     }
 
     fn log(&self, record: &log::Record) {
         if self.enabled(record.metadata()) {
+            if record.level() >= log::Level::Debug {
+                if let Some(sampler) = self
+                    .samplers
+                    .iter()
+                    .find(|s| record.target().contains(&s.target))
+                {
+                    match sampler.sample() {
+                        (false, _) => return,
+                        // through this logger directly, it may not be the global one
+                        (true, Some(dropped)) => self.log(
+                            &log::Record::builder()
+                                .level(log::Level::Info)
+                                .target(record.target())
+                                .file(record.file())
+                                .line(record.line())
+                                .args(format_args!(
+                                    "Sampling dropped {dropped} record from target {}",
+                                    sampler.target
+                                ))
+                                .build(),
+                        ),
+                        (true, None) => {}
+                    }
+                }
+            }
             let now = Local::now();
             let timestamp = now.format("%Y-%m-%d %H:%M:%S").to_string();
             let ts = now.timestamp();
@@ -143,36 +426,66 @@ impl log::Log for Mylogger {
             let line = record.line().unwrap_or(0);
             //
             let print = format!(
-                "[{}] [{}] - [{}] [{}:{}] - {}",
+                "[{}] [{}] - [{}] [{}:{}] - {}{}",
                 timestamp,
                 record.level(),
                 record.target(),
                 file,
                 line,
-                record.args()
+                record.args(),
+                key_values(record)
             );
             println!("{}", print);
+            let config = self.config.read().unwrap();
             #[cfg(feature = "discord")]
             {
                 use log::Level;
-                if record.level() <= Level::Info {
+                if record.level() <= Level::Info
+                    && config.webhook_url.is_some()
+                    && self.backoff.ready()
+                {
                     let s = self.clone();
                     let mut print = print.clone();
                     print = print.replace(&timestamp, &timest(ts));
                     if record.level() == Level::Error {
-                        print = format!("{print} {}", tags(self.tag.clone().unwrap_or_default()));
+                        print = format!("{print} {}", tags(config.tag.clone().unwrap_or_default()));
                     }
-                    tokio::spawn(async move { s.send_message(&print).await });
+                    s.pending.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(async move {
+                        s.send_message(&print).await;
+                        s.pending.fetch_sub(1, Ordering::SeqCst);
+                    });
                 }
             }
-            if let Some(file) = &self.file {
-                let mut f = file.lock().unwrap();
-                writeln!(*f, "{print}").ok();
+            if let Some(file) = &config.file {
+                file.write(record.level(), print.clone());
+            }
+            let sinks = sinks(&config);
+            drop(config);
+            for sink in sinks {
+                sink.write(record.level(), &print);
             }
         }
     }
 
-    fn flush(&self) {}
+    /// sync the file and wait up to [`FLUSH_TIMEOUT`] for in flight webhook send
+    /// this block the thread, on current thread tokio runtime the webhook cant progress
+    /// so it will only wait out the timeout
+    fn flush(&self) {
+        let config = self.config.read().unwrap();
+        if let Some(file) = &config.file {
+            file.flush(FLUSH_TIMEOUT);
+        }
+        let sinks = sinks(&config);
+        drop(config);
+        for sink in sinks {
+            sink.flush();
+        }
+        let deadline = std::time::Instant::now() + FLUSH_TIMEOUT;
+        while self.pending.load(Ordering::SeqCst) > 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
 }
 
 #[cfg(not(feature = "discord"))]
@@ -184,6 +497,155 @@ fn name() {
     log::info!("hello info");
     log::warn!("hello warn");
     log::error!("hello error");
+    log::info!(request_id = 42, user = "agus"; "hello kv");
+}
+
+// every record is dropped with the `disabled` feature
+#[cfg(not(feature = "disabled"))]
+#[test]
+fn reconfigure_sink_while_running() {
+    use log::Log;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<std::sync::Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let logger = Mylogger::default();
+    let running = logger.clone();
+    let log = |msg: &str| {
+        running.log(
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .target("app")
+                .args(format_args!("{msg}"))
+                .build(),
+        )
+    };
+    let out = Shared::default();
+    log("before");
+    let id = logger.add_sink(out.clone(), log::LevelFilter::Warn);
+    log("during");
+    assert!(logger.remove_sink(id));
+    assert!(!logger.remove_sink(id));
+    log("after");
+
+    let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(out.lines().count(), 1);
+    assert!(out.ends_with("- during\n"));
+}
+
+#[cfg(not(feature = "disabled"))]
+#[test]
+fn sink_writer_can_log() {
+    use log::Log;
+
+    /// log and reconfigure the logger from inside the sink
+    struct Reentrant(Mylogger);
+    impl Write for Reentrant {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.remove_sink(SinkId(u64::MAX));
+            self.0.log(
+                &log::Record::builder()
+                    .level(log::Level::Warn)
+                    .args(format_args!("from sink"))
+                    .build(),
+            );
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let logger = Mylogger::default();
+    let id = logger.add_sink(Reentrant(logger.clone()), log::LevelFilter::Warn);
+    logger.log(
+        &log::Record::builder()
+            .level(log::Level::Warn)
+            .args(format_args!("outer"))
+            .build(),
+    );
+    assert!(logger.remove_sink(id));
+}
+
+#[test]
+fn chunk_long_message() {
+    let msg = format!("{}\n{}", "a".repeat(1500), "b".repeat(1500));
+    let chunks = chunk_message(&msg, DISCORD_MESSAGE_LIMIT);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks.concat(), msg);
+    assert!(chunks
+        .iter()
+        .all(|c| c.chars().count() <= DISCORD_MESSAGE_LIMIT));
+
+    assert_eq!(chunk_message(&"é".repeat(5), 2), ["éé", "éé", "é"]);
+}
+
+/// webhook answering every request with the next status line, return its url and hit count
+#[cfg(all(test, feature = "discord"))]
+fn mock_webhook(statuses: Vec<&'static str>) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+    use std::{
+        io::{BufRead, BufReader, Read},
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = hits.clone();
+    std::thread::spawn(move || {
+        for status in statuses {
+            let Ok((mut stream, _)) = listener.accept() else {
+                return;
+            };
+            // whole request so closing doesnt reset the connection under the client
+            let mut reader = BufReader::new(&stream);
+            let mut len = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    len = value.trim().parse().unwrap_or(0);
+                }
+                line.clear();
+            }
+            reader.read_exact(&mut vec![0; len]).ok();
+            counter.fetch_add(1, Ordering::SeqCst);
+            let body = r#"{"retry_after":0.01}"#;
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .ok();
+        }
+    });
+    (url, hits)
+}
+
+#[cfg(feature = "discord")]
+#[tokio::test]
+async fn dead_webhook_back_off() {
+    let (url, _) = mock_webhook(vec!["404 Not Found", "204 No Content"]);
+    let logger = Mylogger::webhook_url(url, "test");
+    logger.send_message("hello").await;
+    assert_eq!(logger.webhook_backoff().failures, 1);
+}
+
+#[cfg(feature = "discord")]
+#[tokio::test]
+async fn rate_limited_chunk_drop_the_rest() {
+    let limited = "429 Too Many Requests";
+    let (url, hits) = mock_webhook(vec![limited, limited, limited, "204 No Content"]);
+    let logger = Mylogger::webhook_url(url, "test");
+    let msg = format!("{}\n{}", "a".repeat(1500), "b".repeat(1500));
+    logger.send_message(&msg).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(logger.webhook_backoff().failures, 1);
 }
 
 #[cfg(feature = "discord")]
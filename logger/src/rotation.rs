@@ -0,0 +1,168 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// codec of rolled log segment, the active file is never compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// `app.log.1.gz`, needs the `gzip` feature
+    #[cfg(feature = "gzip")]
+    Gzip,
+}
+
+/// suffix of every codec, so segment of previous policy still get shifted and pruned
+const EXTENSIONS: [&str; 2] = ["", ".gz"];
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::None => "",
+            #[cfg(feature = "gzip")]
+            Self::Gzip => ".gz",
+        }
+    }
+
+    /// replace the rolled segment with its compressed one, written aside then renamed
+    fn compress(self, rolled: &Path) -> io::Result<()> {
+        match self {
+            Self::None => Ok(()),
+            #[cfg(feature = "gzip")]
+            Self::Gzip => {
+                use flate2::write::GzEncoder;
+
+                let target = segment_name(rolled, self.extension());
+                let temp = segment_name(&target, ".tmp");
+                let written = (|| {
+                    let mut encoder =
+                        GzEncoder::new(File::create(&temp)?, flate2::Compression::default());
+                    io::copy(&mut File::open(rolled)?, &mut encoder)?;
+                    encoder.finish()?.sync_all()?;
+                    fs::rename(&temp, &target)
+                })();
+                if let Err(e) = written {
+                    fs::remove_file(&temp).ok();
+                    return Err(e);
+                }
+                fs::remove_file(rolled)
+            }
+        }
+    }
+}
+
+/// size based rotation of the log file, set with
+/// [`Mylogger::set_rotation`](crate::Mylogger::set_rotation)
+/// rolled on the file thread so compressing doesnt slow the log call down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// roll the active file once its this big
+    pub max_bytes: u64,
+    /// rolled segment kept, `app.log.1` is the newest one
+    pub keep: usize,
+    pub compression: Compression,
+}
+
+impl RotationPolicy {
+    /// roll every `max_bytes` keeping 5 uncompressed segment
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            keep: 5,
+            compression: Compression::None,
+        }
+    }
+
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+}
+
+fn segment_name(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn segment(path: &Path, n: usize, extension: &str) -> PathBuf {
+    segment_name(path, &format!(".{n}{extension}"))
+}
+
+/// move the active file to `path.1`, shifting the older one up and dropping the one past
+/// [`RotationPolicy::keep`], then open fresh active file before compressing the rolled one
+/// so failed compression only leave it uncompressed
+pub(crate) fn roll(path: &Path, policy: &RotationPolicy) -> io::Result<File> {
+    if policy.keep == 0 {
+        fs::remove_file(path)?;
+        return open(path);
+    }
+    for ext in EXTENSIONS {
+        fs::remove_file(segment(path, policy.keep, ext)).ok();
+    }
+    for n in (1..policy.keep).rev() {
+        for ext in EXTENSIONS {
+            let from = segment(path, n, ext);
+            if from.exists() {
+                fs::rename(&from, segment(path, n + 1, ext))?;
+            }
+        }
+    }
+    let rolled = segment(path, 1, "");
+    fs::rename(path, &rolled)?;
+    let active = open(path)?;
+    if let Err(e) = policy.compression.compress(&rolled) {
+        eprintln!("Failed to compress {rolled:?}: {e}");
+    }
+    Ok(active)
+}
+
+/// active log file, appended so failed rotation can reopen it safely
+pub(crate) fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().append(true).create(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_and_prune_segment() {
+        let dir = std::env::temp_dir().join(format!("logger-rotation-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let policy = RotationPolicy::new(0).keep(2);
+        for content in ["a", "b", "c"] {
+            fs::write(&path, content).unwrap();
+            roll(&path, &policy).unwrap();
+        }
+        let read = |n| fs::read_to_string(segment(&path, n, "")).unwrap();
+        assert_eq!((read(1), read(2)), ("c".to_string(), "b".to_string()));
+        assert!(!segment(&path, 3, "").exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Read;
+
+            let policy = policy.compression(Compression::Gzip);
+            fs::write(&path, "d").unwrap();
+            roll(&path, &policy).unwrap();
+            let mut content = String::new();
+            flate2::read::GzDecoder::new(File::open(segment(&path, 1, ".gz")).unwrap())
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, "d");
+            assert!(!segment(&path, 1, "").exists());
+            assert_eq!(read(2), "c");
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// how often the dropped count of sampled target is reported
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// sampling applied to debug/trace record of a target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleRate {
+    /// keep 1 record every n
+    OneIn(u64),
+    /// keep at most n record per second
+    PerSecond(u64),
+}
+
+#[derive(Debug)]
+struct State {
+    seen: u64,
+    window: Instant,
+    window_count: u64,
+    dropped: u64,
+    last_report: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    pub(crate) target: String,
+    rate: SampleRate,
+    state: Mutex<State>,
+}
+
+impl Sampler {
+    pub(crate) fn new(target: impl ToString, rate: SampleRate) -> Self {
+        let now = Instant::now();
+        Self {
+            target: target.to_string(),
+            rate,
+            state: Mutex::new(State {
+                seen: 0,
+                window: now,
+                window_count: 0,
+                dropped: 0,
+                last_report: now,
+            }),
+        }
+    }
+
+    /// decide whether the record is kept
+    /// also return the dropped count when its time to report it
+    pub(crate) fn sample(&self) -> (bool, Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let keep = match self.rate {
+            SampleRate::OneIn(n) => {
+                state.seen += 1;
+                n <= 1 || state.seen % n == 1
+            }
+            SampleRate::PerSecond(n) => {
+                if now.duration_since(state.window) >= Duration::from_secs(1) {
+                    state.window = now;
+                    state.window_count = 0;
+                }
+                state.window_count += 1;
+                state.window_count <= n
+            }
+        };
+        if !keep {
+            state.dropped += 1;
+            return (false, None);
+        }
+        if state.dropped > 0 && now.duration_since(state.last_report) >= REPORT_INTERVAL {
+            state.last_report = now;
+            return (true, Some(std::mem::take(&mut state.dropped)));
+        }
+        (true, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_in_n() {
+        let sampler = Sampler::new("hot", SampleRate::OneIn(3));
+        let kept = (0..9).filter(|_| sampler.sample().0).count();
+        assert_eq!(kept, 3);
+
+        let sampler = Sampler::new("hot", SampleRate::PerSecond(2));
+        let kept = (0..9).filter(|_| sampler.sample().0).count();
+        assert_eq!(kept, 2);
+    }
+}
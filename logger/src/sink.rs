@@ -0,0 +1,67 @@
+use std::{cell::Cell, io::Write, sync::Mutex};
+
+thread_local! {
+    /// set while this thread write into a sink, so its writer logging doesnt lock it again
+    static WRITING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// handle of sink added with [`Mylogger::add_sink`](crate::Mylogger::add_sink), to remove it later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinkId(pub(crate) u64);
+
+/// extra output of the formatted line, e.g. syslog socket or in app buffer
+pub(crate) struct Sink {
+    level: log::LevelFilter,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Sink {
+    pub(crate) fn new(writer: impl Write + Send + 'static, level: log::LevelFilter) -> Self {
+        Self {
+            level,
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// write the line when the level pass the sink filter, error is ignored like file sink
+    /// line logged by the sink writer itself only reach the other output
+    pub(crate) fn write(&self, level: log::Level, line: &str) {
+        if level > self.level || WRITING.replace(true) {
+            return;
+        }
+        writeln!(self.writer.lock().unwrap(), "{line}").ok();
+        WRITING.set(false);
+    }
+
+    pub(crate) fn flush(&self) {
+        self.writer.lock().unwrap().flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn filter_by_own_level() {
+        let out = Shared::default();
+        let sink = Sink::new(out.clone(), log::LevelFilter::Warn);
+        sink.write(log::Level::Info, "info");
+        sink.write(log::Level::Error, "error");
+        sink.write(log::Level::Warn, "warn");
+        assert_eq!(&*out.0.lock().unwrap(), b"error\nwarn\n");
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+/// encoding of a frame, negotiated by the first byte of every message
+/// the server always answer with the format the client used
+/// ```text
+/// [header: u8][payload...]
+/// ```
+/// `B` is bincode for production client, `J` is json so it can be poked by hand
+/// e.g. `printf 'J"Ping"' | nc -N 127.0.0.1 8080`.
+/// frame without known header is plain bincode from client predating the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Bincode,
+    Json,
+    /// bincode without header byte, answered the same way so old client keep working
+    /// its payload starting with `B`, `J` or [`KEY_HEADER`] is mistaken for header
+    Legacy,
+}
+
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("Received empty frame")]
+    Empty,
+    #[error("Bincode error: {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("Json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Frame payload doesnt start with enum variant")]
+    NoVariant,
+    #[error("Idempotency key must be valid utf8 of at most 255 bytes")]
+    InvalidKey,
+}
+
+/// header of the optional envelope carrying idempotency key in front of the frame
+/// ```text
+/// [K][key len: u8][key...][header: u8][payload...]
+/// ```
+pub const KEY_HEADER: u8 = b'K';
+
+/// wrap encoded frame with the idempotency key envelope
+pub fn with_key(key: &str, frame: &[u8]) -> Result<Vec<u8>, FrameError> {
+    let len = u8::try_from(key.len()).map_err(|_| FrameError::InvalidKey)?;
+    let mut buf = Vec::with_capacity(2 + key.len() + frame.len());
+    buf.extend_from_slice(&[KEY_HEADER, len]);
+    buf.extend_from_slice(key.as_bytes());
+    buf.extend_from_slice(frame);
+    Ok(buf)
+}
+
+/// split the idempotency key envelope off, key is None when the frame doesnt have it
+pub fn split_key(buf: &[u8]) -> Result<(Option<&str>, &[u8]), FrameError> {
+    let Some(rest) = buf.strip_prefix(&[KEY_HEADER]) else {
+        return Ok((None, buf));
+    };
+    let (&len, rest) = rest.split_first().ok_or(FrameError::InvalidKey)?;
+    if rest.len() < len as usize {
+        return Err(FrameError::InvalidKey);
+    }
+    let (key, frame) = rest.split_at(len as usize);
+    let key = std::str::from_utf8(key).map_err(|_| FrameError::InvalidKey)?;
+    Ok((Some(key), frame))
+}
+
+/// leading enum discriminant of a signal, read by [`Format::peek_variant`]
+/// bincode only carry the variant index, json carry its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Variant {
+    Index(u32),
+    Name(String),
+}
+
+impl Format {
+    /// first byte of the frame, None for [`Format::Legacy`]
+    pub const fn header(self) -> Option<u8> {
+        match self {
+            Self::Bincode => Some(b'B'),
+            Self::Json => Some(b'J'),
+            Self::Legacy => None,
+        }
+    }
+
+    pub const fn from_header(header: u8) -> Option<Self> {
+        match header {
+            b'B' => Some(Self::Bincode),
+            b'J' => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// serialize value with the header prepended
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, FrameError> {
+        let mut buf = Vec::from_iter(self.header());
+        match self {
+            Self::Bincode | Self::Legacy => bincode::serialize_into(&mut buf, value)?,
+            Self::Json => serde_json::to_writer(&mut buf, value)?,
+        }
+        Ok(buf)
+    }
+
+    /// format of the frame and its payload, the whole frame when it has no header
+    fn split(buf: &[u8]) -> Result<(Self, &[u8]), FrameError> {
+        let (&header, payload) = buf.split_first().ok_or(FrameError::Empty)?;
+        Ok(match Self::from_header(header) {
+            Some(format) => (format, payload),
+            None => (Self::Legacy, buf),
+        })
+    }
+
+    /// read the header and deserialize the rest of the frame with it
+    pub fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<(Self, T), FrameError> {
+        let (format, payload) = Self::split(buf)?;
+        let value = match format {
+            Self::Bincode | Self::Legacy => bincode::deserialize(payload)?,
+            Self::Json => serde_json::from_slice(payload)?,
+        };
+        Ok((format, value))
+    }
+
+    /// read the header and only the enum discriminant of the payload
+    /// cheap way to route or reject a frame before the full [`Format::decode`]
+    pub fn peek_variant(buf: &[u8]) -> Result<(Self, Variant), FrameError> {
+        let (format, payload) = Self::split(buf)?;
+        let variant = match format {
+            // bincode write the variant index as u32 before its fields
+            Self::Bincode | Self::Legacy => Variant::Index(bincode::deserialize(payload)?),
+            // unit variant is `"Name"`, the others `{"Name": ...}`
+            Self::Json => {
+                let mut rest = payload.trim_ascii_start();
+                if let Some(inner) = rest.strip_prefix(b"{") {
+                    rest = inner.trim_ascii_start();
+                }
+                let name = rest.strip_prefix(b"\"").ok_or(FrameError::NoVariant)?;
+                let mut escaped = false;
+                let end = name
+                    .iter()
+                    .position(|&b| {
+                        let end = b == b'"' && !escaped;
+                        escaped = b == b'\\' && !escaped;
+                        end
+                    })
+                    .ok_or(FrameError::NoVariant)?;
+                Variant::Name(serde_json::from_slice(&rest[..end + 2])?)
+            }
+        };
+        Ok((format, variant))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headerless_frame_is_bincode() {
+        let legacy = bincode::serialize(&(3u32, "restart")).unwrap();
+        let (format, value) = Format::decode::<(u32, String)>(&legacy).unwrap();
+        assert_eq!(format, Format::Legacy);
+        assert_eq!(value, (3, "restart".to_string()));
+        assert_eq!(format.encode(&value).unwrap(), legacy);
+        assert_eq!(
+            Format::peek_variant(&legacy).unwrap(),
+            (Format::Legacy, Variant::Index(3))
+        );
+
+        let headed = Format::Bincode.encode(&value).unwrap();
+        assert_eq!(headed[0], b'B');
+        assert_eq!(
+            Format::decode::<(u32, String)>(&headed).unwrap().0,
+            Format::Bincode
+        );
+        assert!(matches!(Format::decode::<u32>(b""), Err(FrameError::Empty)));
+    }
+}
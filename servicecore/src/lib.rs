@@ -0,0 +1,9 @@
+//! wire format, text protocol and metrics shared by tcpservice and unixservice
+mod format;
+#[cfg(feature = "metrics")]
+mod metrics;
+pub mod text;
+
+pub use format::{split_key, with_key, Format, FrameError, Variant, KEY_HEADER};
+#[cfg(feature = "metrics")]
+pub use metrics::{MessageMetrics, MetricsSnapshot};
@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// number of power of two histogram bucket, the last one hold everything bigger
+const BUCKETS: usize = 32;
+
+/// running size accounting of request/response frame, shared through the server
+/// ```ignore
+/// struct MyService { metrics: MessageMetrics }
+/// // in the server impl
+/// fn metrics(&self) -> Option<&MessageMetrics> { Some(&self.metrics) }
+/// ```
+#[derive(Debug)]
+pub struct MessageMetrics {
+    count: AtomicU64,
+    request_bytes: AtomicU64,
+    response_bytes: AtomicU64,
+    max_request: AtomicU64,
+    max_response: AtomicU64,
+    request_histogram: [AtomicU64; BUCKETS],
+    response_histogram: [AtomicU64; BUCKETS],
+}
+
+/// point in time copy of [`MessageMetrics`]
+/// histogram bucket `i` count frame with size in `[2^(i-1), 2^i)`, bucket 0 is empty frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub count: u64,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub max_request: u64,
+    pub max_response: u64,
+    pub request_histogram: [u64; BUCKETS],
+    pub response_histogram: [u64; BUCKETS],
+}
+
+impl Default for MessageMetrics {
+    fn default() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            request_bytes: AtomicU64::new(0),
+            response_bytes: AtomicU64::new(0),
+            max_request: AtomicU64::new(0),
+            max_response: AtomicU64::new(0),
+            request_histogram: std::array::from_fn(|_| AtomicU64::new(0)),
+            response_histogram: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+fn bucket(size: u64) -> usize {
+    ((u64::BITS - size.leading_zeros()) as usize).min(BUCKETS - 1)
+}
+
+impl MessageMetrics {
+    /// record size of single request and its response in bytes
+    pub fn record(&self, request: usize, response: usize) {
+        let (request, response) = (request as u64, response as u64);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.request_bytes.fetch_add(request, Ordering::Relaxed);
+        self.response_bytes.fetch_add(response, Ordering::Relaxed);
+        self.max_request.fetch_max(request, Ordering::Relaxed);
+        self.max_response.fetch_max(response, Ordering::Relaxed);
+        self.request_histogram[bucket(request)].fetch_add(1, Ordering::Relaxed);
+        self.response_histogram[bucket(response)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let load = |h: &[AtomicU64; BUCKETS]| std::array::from_fn(|i| h[i].load(Ordering::Relaxed));
+        MetricsSnapshot {
+            count: self.count.load(Ordering::Relaxed),
+            request_bytes: self.request_bytes.load(Ordering::Relaxed),
+            response_bytes: self.response_bytes.load(Ordering::Relaxed),
+            max_request: self.max_request.load(Ordering::Relaxed),
+            max_response: self.max_response.load(Ordering::Relaxed),
+            request_histogram: load(&self.request_histogram),
+            response_histogram: load(&self.response_histogram),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_sizes() {
+        let metrics = MessageMetrics::default();
+        metrics.record(0, 3);
+        metrics.record(8, 1000);
+        let snap = metrics.snapshot();
+        assert_eq!(snap.count, 2);
+        assert_eq!(snap.request_bytes, 8);
+        assert_eq!(snap.max_response, 1000);
+        assert_eq!(snap.request_histogram[0], 1);
+        assert_eq!(snap.request_histogram[4], 1);
+        assert_eq!(snap.response_histogram[10], 1);
+    }
+}
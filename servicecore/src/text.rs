@@ -0,0 +1,40 @@
+use std::{fmt::Display, str::FromStr};
+
+/// parse one line of the text protocol into the signal, None for blank line
+/// parse failure is returned already encoded as its `error: ...` reply
+/// the error is rendered here since it may not be Send across the await
+pub fn parse_line<T>(line: &str) -> Option<Result<T, String>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.parse().map_err(|e| encode_reply::<&str, _>(Err(e))))
+}
+
+/// reply line with its newline, `Display` of the response or `error: ...` on failure
+pub fn encode_reply<R: Display, E: Display>(reply: Result<R, E>) -> String {
+    match reply {
+        Ok(response) => format!("{response}\n"),
+        Err(e) => format!("error: {e}\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_encode_line() {
+        assert_eq!(parse_line::<u32>("  \r"), None);
+        assert_eq!(parse_line::<u32>(" 42\r"), Some(Ok(42)));
+        let Some(Err(reply)) = parse_line::<u32>("ping") else {
+            panic!("ping isnt a number");
+        };
+        assert_eq!(reply, "error: invalid digit found in string\n");
+        assert_eq!(encode_reply::<_, &str>(Ok("pong")), "pong\n");
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::debug;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{resolve_base, Sysdir, SysdirError};
+
+/// serialization format of config file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// guess format from file extension, anything other than `.json` is toml
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    fn parse(self, content: &str) -> Result<Value, SysdirError> {
+        Ok(match self {
+            Self::Toml => toml::from_str(content)?,
+            Self::Json => serde_json::from_str(content)?,
+        })
+    }
+
+    fn serialize<T: Serialize>(self, value: &T) -> Result<String, SysdirError> {
+        Ok(match self {
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Json => serde_json::to_string_pretty(value)?,
+        })
+    }
+}
+
+/// merge `overlay` into `base`, table are merged recursively and other value replaced
+fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                match base.get_mut(&k) {
+                    Some(b) => deep_merge(b, v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+impl Sysdir {
+    /// path of file on system config dir, ignoring debug setting
+    fn system_config_path(&self, file: impl AsRef<Path>) -> Option<PathBuf> {
+        resolve_base(dirs::config_dir()).map(|p| p.join(&self.app_name).join(file))
+    }
+
+    /// read config from system config dir then overlay it with the one on current dir
+    /// both are optional but at least one must exist, local value win on conflict
+    pub fn load_layered<T: DeserializeOwned>(
+        &self,
+        file: impl AsRef<Path>,
+    ) -> Result<T, SysdirError> {
+        let file = file.as_ref();
+        let format = ConfigFormat::from_path(file);
+        let local = Path::new(".").join(file);
+
+        let mut merged: Option<Value> = None;
+        for path in self.system_config_path(file).into_iter().chain([local]) {
+            if !path.exists() {
+                continue;
+            }
+            debug!("Loading config layer {path:?}");
+            let value = format.parse(&std::fs::read_to_string(&path)?)?;
+            match &mut merged {
+                Some(base) => deep_merge(base, value),
+                None => merged = Some(value),
+            }
+        }
+
+        let merged = merged.ok_or_else(|| SysdirError::NotFound(file.to_path_buf()))?;
+        Ok(serde_json::from_value(merged)?)
+    }
+
+    /// write config into [`Sysdir::config_dir`] path without ever leaving it half written
+    /// the content is written to temp file on the same dir then renamed over the target,
+    /// the temp name is unique per writer so concurrent writes dont clobber each other
+    pub fn write_config_atomic<T: Serialize>(
+        &self,
+        file: impl AsRef<Path>,
+        value: &T,
+        format: ConfigFormat,
+    ) -> Result<PathBuf, SysdirError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let file = file.as_ref();
+        let target = self
+            .config_dir(file)
+            .path
+            .filter(|p| p.file_name().is_some())
+            .ok_or_else(|| SysdirError::NoPath(file.to_path_buf()))?;
+        let content = format.serialize(value)?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp_name = target.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp = target.with_file_name(temp_name);
+
+        debug!("Writing config to {temp:?}");
+        let written = File::create(&temp).and_then(|mut f| {
+            f.write_all(content.as_bytes())?;
+            f.sync_all()
+        });
+        if let Err(e) = written.and_then(|_| fs::rename(&temp, &target)) {
+            fs::remove_file(&temp).ok();
+            return Err(e.into());
+        }
+        // persist the rename itself, not every platform allow opening dir so ignore failure
+        if let Some(parent) = target.parent() {
+            File::open(parent).and_then(|d| d.sync_all()).ok();
+        }
+        debug!("Config written to {target:?}");
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn concurrent_atomic_write() {
+        let dir = Sysdir::default().set_debug(true);
+        let file = format!("target/atomic-test-{}.json", std::process::id());
+        std::fs::create_dir_all("target").unwrap();
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let (dir, file) = (dir.clone(), file.clone());
+                std::thread::spawn(move || {
+                    dir.write_config_atomic(&file, &json!({ "writer": i }), ConfigFormat::Json)
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        let value: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+        assert!(value["writer"].is_u64());
+        fs::remove_file(&file).ok();
+
+        assert!(matches!(
+            dir.write_config_atomic("", &json!({}), ConfigFormat::Json),
+            Err(SysdirError::NoPath(_))
+        ));
+    }
+
+    #[test]
+    fn merge_local_over_system() {
+        let mut base = json!({"name": "app", "db": {"host": "localhost", "port": 5432}});
+        let overlay = json!({"db": {"port": 6543}, "debug": true});
+        deep_merge(&mut base, overlay);
+        assert_eq!(
+            base,
+            json!({"name": "app", "db": {"host": "localhost", "port": 6543}, "debug": true})
+        );
+    }
+}
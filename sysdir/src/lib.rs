@@ -1,12 +1,74 @@
 #![allow(unused)]
 
+use appname::exe_name;
 use log::{debug, error};
 use std::{
+    ffi::OsString,
     fmt::{Debug, Display},
     path::{Path, PathBuf},
 };
+use thiserror::Error;
+
+mod config;
+#[cfg(unix)]
+mod lock;
+pub use config::ConfigFormat;
+#[cfg(unix)]
+pub use lock::LockGuard;
+
+#[derive(Error, Debug)]
+pub enum SysdirError {
+    #[error("Config file not found: {0}")]
+    NotFound(PathBuf),
+    #[error("No config path for: {0}")]
+    NoPath(PathBuf),
+    #[error("Lock is held by another instance: {0}")]
+    Locked(PathBuf),
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Failed to parse toml: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Failed to serialize toml: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+    #[error("Json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// env var used as base directory when the system one is missing or not absolute
+pub const CONFIG_DIR_ENV: &str = "APP_CONFIG_DIR";
+
+/// default env var used to pick environment specific config, e.g. `dev`, `prod`
+pub const APP_ENV: &str = "APP_ENV";
+
+/// make sure the base dir from `dirs` is usable
+/// some sandboxed CI/container give empty or relative path, fallback to [`CONFIG_DIR_ENV`]
+fn resolve_base(sys: Option<PathBuf>) -> Option<PathBuf> {
+    resolve_base_with(sys, std::env::var_os(CONFIG_DIR_ENV))
+}
+
+/// [`resolve_base`] with the value of [`CONFIG_DIR_ENV`] given, only absolute one is used
+fn resolve_base_with(sys: Option<PathBuf>, env: Option<OsString>) -> Option<PathBuf> {
+    if let Some(p) = sys {
+        if p.is_absolute() && p.exists() {
+            return Some(p);
+        }
+        debug!("System dir {p:?} is not usable, trying {CONFIG_DIR_ENV}");
+    }
+    match env.map(PathBuf::from) {
+        Some(p) if p.is_absolute() => Some(p),
+        Some(p) => {
+            error!("{CONFIG_DIR_ENV} {p:?} is not absolute, ignoring it");
+            None
+        }
+        None => {
+            error!("No usable base dir, set {CONFIG_DIR_ENV} to override");
+            None
+        }
+    }
+}
 
 /// generelize patn for system app
+/// app name default to the running executable name, see [`Sysdir::custom_name`]
 #[derive(Clone, Debug)]
 pub struct Sysdir {
     debug: bool,
@@ -16,10 +78,12 @@ pub struct Sysdir {
 }
 
 impl Default for Sysdir {
+    /// app name default to the running executable name, e.g. `myapp` for `target/release/myapp`
+    /// so config land in `<config dir>/myapp`, this crate name when it cant be resolved
     fn default() -> Self {
         Self {
             debug: false,
-            app_name: env!("CARGO_PKG_NAME").to_string(),
+            app_name: exe_name(env!("CARGO_PKG_NAME")),
             path: None,
             file: None,
         }
@@ -62,7 +126,9 @@ impl Sysdir {
     }
 
     fn _add_name(&self, sys: Option<PathBuf>) -> PathBuf {
-        sys.unwrap_or(PathBuf::from(".")).join(&self.app_name)
+        resolve_base(sys)
+            .unwrap_or(PathBuf::from("."))
+            .join(&self.app_name)
     }
     fn path(&self, file: impl AsRef<Path>, _sys: Option<PathBuf>) -> Self {
         let mut x = self.clone();
@@ -100,6 +166,36 @@ impl Sysdir {
     pub fn config_dir(&self, file: impl AsRef<Path>) -> Self {
         self.path(file, dirs::config_dir())
     }
+    /// find `base.{env}.ext` on config dir first then fallback to plain `base.ext`
+    /// the env name is read from `env_var`, default to [`APP_ENV`]
+    /// when nothing exist, return the plain config path so it can be created
+    pub fn find_env_path(&self, base: impl AsRef<Path>, env_var: Option<&str>) -> Self {
+        let env = std::env::var(env_var.unwrap_or(APP_ENV)).ok();
+        self.env_path(base.as_ref(), env)
+    }
+
+    /// [`Sysdir::find_env_path`] with the env name given
+    fn env_path(&self, base: &Path, env: Option<String>) -> Self {
+        let plain = self.config_dir(base);
+
+        let env = match env {
+            Some(env) if !env.is_empty() => env,
+            _ => return plain,
+        };
+        let mut name = base.file_stem().unwrap_or_default().to_os_string();
+        name.push(format!(".{env}"));
+        if let Some(ext) = base.extension() {
+            name.push(".");
+            name.push(ext);
+        }
+
+        let env_path = self.config_dir(base.with_file_name(name));
+        if env_path.path.as_ref().is_some_and(|p| p.exists()) {
+            debug!("Using {env} config {:?}", env_path.path);
+            return env_path;
+        }
+        plain
+    }
     pub fn log_dir(&self, file: impl AsRef<Path>) -> Self {
         self.path(Path::new("logs").join(file.as_ref()), dirs::config_dir())
     }
@@ -119,4 +215,47 @@ mod tests {
         println!("{}", x.assets_dir("myasset.txt"));
         println!("{}", x.config_dir("myconfig.txt"));
     }
+
+    #[test]
+    fn env_specific_config() {
+        let dir = Sysdir::default().set_debug(true);
+        let base = format!("target/env-test-{}.toml", std::process::id());
+        let env_file = format!("target/env-test-{}.staging.toml", std::process::id());
+        std::fs::create_dir_all("target").unwrap();
+        std::fs::write(&env_file, "").unwrap();
+
+        let env_path = |env: &str| {
+            dir.env_path(Path::new(&base), Some(env.to_string()))
+                .to_string()
+        };
+        assert_eq!(env_path("staging"), dir.config_dir(&env_file).to_string());
+        assert_eq!(env_path("prod"), dir.config_dir(&base).to_string());
+        assert_eq!(
+            dir.env_path(Path::new(&base), None).to_string(),
+            dir.config_dir(&base).to_string()
+        );
+        std::fs::remove_file(env_file).ok();
+    }
+
+    #[test]
+    fn missing_system_dir() {
+        let tmp = std::env::temp_dir();
+        assert_eq!(
+            resolve_base_with(Some(tmp.clone()), None),
+            Some(tmp.clone())
+        );
+
+        assert_eq!(resolve_base_with(Some(PathBuf::new()), None), None);
+        assert_eq!(resolve_base_with(Some("relative".into()), None), None);
+        assert_eq!(resolve_base_with(None, None), None);
+
+        let env = Some(tmp.clone().into_os_string());
+        assert_eq!(
+            resolve_base_with(Some(PathBuf::new()), env.clone()),
+            Some(tmp.clone())
+        );
+        assert_eq!(resolve_base_with(None, env), Some(tmp));
+        assert_eq!(resolve_base_with(None, Some("relative".into())), None);
+        assert_eq!(resolve_base_with(None, Some("".into())), None);
+    }
 }
@@ -0,0 +1,86 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+use crate::{Sysdir, SysdirError};
+
+/// single instance lock, released when dropped
+/// the lock is held by `flock` so crashed process wont leave stale lock behind
+#[derive(Debug)]
+pub struct LockGuard {
+    path: PathBuf,
+    file: File,
+}
+
+impl LockGuard {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // the file is kept, removing it would race with other instance opening it
+        if unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) } != 0 {
+            warn!("Failed to release lock {:?}", self.path);
+        }
+        debug!("Released lock {:?}", self.path);
+    }
+}
+
+impl Sysdir {
+    /// create and lock `name` on [`Sysdir::config_dir`]
+    /// return [`SysdirError::Locked`] when other instance already hold it
+    pub fn acquire_lock(&self, name: impl AsRef<Path>) -> Result<LockGuard, SysdirError> {
+        let path = self.config_dir(name).path.unwrap_or_default();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+                let owner = fs::read_to_string(&path).unwrap_or_default();
+                warn!("Lock {path:?} is held by pid {}", owner.trim());
+                return Err(SysdirError::Locked(path));
+            }
+            return Err(err.into());
+        }
+
+        // lock from crashed process is released by the kernel, only the pid is stale
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        debug!("Acquired lock {path:?}");
+        Ok(LockGuard { path, file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_lock_fails() {
+        let dir = Sysdir::custom_name("sysdir-lock-test").set_debug(true);
+        let name = format!("target/test-{}.lock", std::process::id());
+        let guard = dir.acquire_lock(&name).unwrap();
+        assert!(matches!(
+            dir.acquire_lock(&name),
+            Err(SysdirError::Locked(_))
+        ));
+        drop(guard);
+        let guard = dir.acquire_lock(&name).unwrap();
+        fs::remove_file(guard.path()).ok();
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use tokio::{sync::watch, task::JoinHandle};
+
+/// shared between the accept loops and the [`ServiceHandle`]
+#[derive(Debug, Clone)]
+pub struct ServiceState {
+    active: Arc<AtomicUsize>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl ServiceState {
+    pub(crate) fn new() -> (Self, watch::Sender<bool>) {
+        let (tx, rx) = watch::channel(false);
+        let state = Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            shutdown: rx,
+        };
+        (state, tx)
+    }
+
+    /// resolve once shutdown is requested
+    pub(crate) async fn shutdown_requested(&mut self) {
+        // sender dropped without shutdown means nobody can ask anymore, wait forever
+        if self.shutdown.wait_for(|s| *s).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// count the connection as active until the guard is dropped
+    pub(crate) fn connection(&self) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(self.active.clone())
+    }
+}
+
+pub(crate) struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Control of service spawned by [`TcpServiceServer::start_service`](crate::TcpServiceServer::start_service)
+#[derive(Debug)]
+pub struct ServiceHandle {
+    pub(crate) active: Arc<AtomicUsize>,
+    pub(crate) shutdown: watch::Sender<bool>,
+    /// resolve when every accept loop stopped or one of them failed
+    pub handle: JoinHandle<std::io::Result<()>>,
+}
+
+impl ServiceHandle {
+    pub(crate) fn new(
+        state: &ServiceState,
+        shutdown: watch::Sender<bool>,
+        handle: JoinHandle<std::io::Result<()>>,
+    ) -> Self {
+        Self {
+            active: state.active.clone(),
+            shutdown,
+            handle,
+        }
+    }
+
+    /// stop accepting new connection, in flight request still finish
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// connection currently being served
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+}
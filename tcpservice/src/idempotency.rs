@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// response cache keyed by the idempotency key of the request, keep it in the service struct
+/// so a client retrying a request whose response was lost doesnt run the handler twice
+/// ```ignore
+/// struct MyService { cache: IdempotencyCache }
+/// // in the server impl
+/// fn idempotency(&self) -> Option<&IdempotencyCache> { Some(&self.cache) }
+/// ```
+/// duplicate arriving while the first one is still handled is not deduplicated
+#[derive(Debug)]
+pub struct IdempotencyCache {
+    entries: Mutex<HashMap<String, (Instant, Vec<u8>)>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl Default for IdempotencyCache {
+    /// 1024 response for 5 minutes
+    fn default() -> Self {
+        Self::new(1024, Duration::from_secs(300))
+    }
+}
+
+impl IdempotencyCache {
+    /// keep at most `capacity` response each for `ttl`, the oldest is evicted first
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// encoded response of the key if its not expired yet
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(at, _)| at.elapsed() < self.ttl)
+            .map(|(_, response)| response.clone())
+    }
+
+    pub fn insert(&self, key: impl ToString, response: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        while entries.len() >= self.capacity {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(key.to_string(), (Instant::now(), response));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,16 +1,39 @@
 #![allow(async_fn_in_trait)]
 
-use bincode::{deserialize, serialize};
-use log::{debug, error};
+use log::{debug, error, warn};
 use serde::{de::DeserializeOwned, Serialize};
-use std::sync::Arc;
+use std::{
+    io::ErrorKind,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    net::{lookup_host, TcpListener, TcpSocket, TcpStream, ToSocketAddrs},
     spawn,
+    task::JoinSet,
+    time::sleep,
 };
 
+mod handle;
+mod idempotency;
+#[cfg(feature = "tracing")]
+mod span;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "text")]
+mod text;
+
+pub use handle::{ServiceHandle, ServiceState};
+pub use idempotency::IdempotencyCache;
+pub use servicecore::{split_key, with_key, Format, FrameError, Variant, KEY_HEADER};
+#[cfg(feature = "metrics")]
+pub use servicecore::{MessageMetrics, MetricsSnapshot};
+#[cfg(feature = "text")]
+pub use text::TextServer;
+
 pub trait TcpServiceClient: Sized + Send + Sync + 'static {
     /// this type better serve as signal (enum)
     type Signal: Serialize + DeserializeOwned + Send + Sync + 'static;
@@ -20,30 +43,92 @@ pub trait TcpServiceClient: Sized + Send + Sync + 'static {
     /// required to connect to socket name
     fn address() -> String;
 
+    /// encoding used to talk with the server, json is handy for debugging
+    fn format() -> Format {
+        Format::Bincode
+    }
+
+    /// check the server is up without sending real signal
+    /// send empty request which the server answer with empty response, same as unixservice
+    async fn ping() -> Result<(), Box<dyn std::error::Error>> {
+        Self::ping_to(Self::address()).await
+    }
+
+    /// same as [`TcpServiceClient::ping`] on the given address
+    async fn ping_to(address: impl ToSocketAddrs) -> Result<(), Box<dyn std::error::Error>> {
+        if !round_trip(address, &[]).await?.is_empty() {
+            return Err("Unexpected ping response".into());
+        }
+        Ok(())
+    }
+
     /// send signal into server
     async fn send_request(
         self: Arc<Self>,
         signal: Self::Signal,
     ) -> Result<Self::Response, Box<dyn std::error::Error>> {
-        match TcpStream::connect(Self::address()).await {
-            Ok(mut stream) => {
-                let msg = serialize(&signal)?;
-                if let Err(e) = stream.write_all(&msg).await {
-                    error!("Error writing to stream: {}", e);
-                    return Err(Box::new(e));
-                }
+        self.send_request_to(Self::address(), signal).await
+    }
 
-                let mut buf = vec![];
-                if let Err(e) = stream.read_to_end(&mut buf).await {
-                    error!("Error reading from stream: {}", e);
-                    return Err(Box::new(e));
-                }
-                Ok(deserialize(&buf)?)
+    /// send signal into server on the given address instead of [`TcpServiceClient::address`]
+    async fn send_request_to(
+        self: Arc<Self>,
+        address: impl ToSocketAddrs,
+        signal: Self::Signal,
+    ) -> Result<Self::Response, Box<dyn std::error::Error>> {
+        let msg = Self::format().encode(&signal)?;
+        Ok(Format::decode(&round_trip(address, &msg).await?)?.1)
+    }
+
+    /// send signal with idempotency key, server caching response of the key
+    /// answer the retry with the cached response instead of handling it again
+    async fn send_idempotent(
+        self: Arc<Self>,
+        key: &str,
+        signal: Self::Signal,
+    ) -> Result<Self::Response, Box<dyn std::error::Error>> {
+        self.send_idempotent_to(Self::address(), key, signal).await
+    }
+
+    /// same as [`TcpServiceClient::send_idempotent`] on the given address
+    async fn send_idempotent_to(
+        self: Arc<Self>,
+        address: impl ToSocketAddrs,
+        key: &str,
+        signal: Self::Signal,
+    ) -> Result<Self::Response, Box<dyn std::error::Error>> {
+        let msg = with_key(key, &Self::format().encode(&signal)?)?;
+        Ok(Format::decode(&round_trip(address, &msg).await?)?.1)
+    }
+}
+
+/// write the whole request then read the whole response
+async fn round_trip(
+    address: impl ToSocketAddrs,
+    msg: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match TcpStream::connect(address).await {
+        Ok(mut stream) => {
+            if let Err(e) = stream.write_all(msg).await {
+                error!("Error writing to stream: {}", e);
+                return Err(Box::new(e));
             }
-            Err(e) => {
-                error!("Error connecting to socket: {}", e);
-                Err(Box::new(e))
+            // server read until EOF, so close our write half to end the request
+            if let Err(e) = stream.shutdown().await {
+                error!("Error closing write stream: {}", e);
+                return Err(Box::new(e));
             }
+
+            let mut buf = vec![];
+            if let Err(e) = stream.read_to_end(&mut buf).await {
+                error!("Error reading from stream: {}", e);
+                return Err(Box::new(e));
+            }
+            Ok(buf)
+        }
+        Err(e) => {
+            error!("Error connecting to socket: {}", e);
+            Err(Box::new(e))
         }
     }
 }
@@ -61,7 +146,57 @@ pub trait TcpServiceServer: Sized + Sync + Send + 'static {
 
     fn address() -> String;
 
+    /// Every address to listen on, e.g. both `0.0.0.0:8080` and `[::]:8080` for dual stack.
+    /// Each one is resolved so host name bind to all of its addresses.
+    /// Default to [`TcpServiceServer::address`].
+    /// Note on linux `[::]` usually accept ipv4 too, binding `0.0.0.0` on same port then fail.
+    fn addresses() -> Vec<String> {
+        vec![Self::address()]
+    }
+
+    /// Set `SO_REUSEADDR` so a quick restart can rebind while the old socket is in TIME_WAIT.
+    /// On unix it doesnt allow binding over a port another socket is actively listening on,
+    /// on windows it does, so another process could hijack the port. Default to true.
+    fn reuse_address() -> bool {
+        true
+    }
+
+    /// How many times binding is retried when the address is in use. Default to 0.
+    fn bind_retries() -> u32 {
+        0
+    }
+
+    /// Wait before the first bind retry, doubled on every retry. Default to 200ms.
+    fn bind_backoff() -> Duration {
+        Duration::from_millis(200)
+    }
+
+    /// Request taking longer than this is logged as warning.
+    /// Default to 1 second.
+    fn slow_request_threshold() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Cheap check on the signal enum variant before the full deserialize,
+    /// e.g. reject variant the server doesnt handle. Default to accept everything.
+    fn accept_variant(_variant: &Variant) -> bool {
+        true
+    }
+
+    /// Size accounting of every request/response, keep it in the service struct.
+    #[cfg(feature = "metrics")]
+    fn metrics(&self) -> Option<&MessageMetrics> {
+        None
+    }
+
+    /// Response cache for request sent with idempotency key, keep it in the service struct.
+    /// Request with key is handled every time when its None, the default.
+    fn idempotency(&self) -> Option<&IdempotencyCache> {
+        None
+    }
+
     /// Handle an incoming request.
+    /// Run inside the request span with the `tracing` feature, so use `tracing` macros to correlate.
     async fn handle_request(
         self: Arc<Self>,
         signal: Self::Signal,
@@ -69,34 +204,198 @@ pub trait TcpServiceServer: Sized + Sync + Send + 'static {
 
     /// Create and run the TCP service.
     async fn create_service(self) -> Result<(), Box<dyn std::error::Error>> {
+        let service = self.start_service().await?;
+        Ok(service.handle.await??)
+    }
+
+    /// Bind every [`TcpServiceServer::addresses`] and run the service in background.
+    /// Return handle to shut it down and inspect it.
+    async fn start_service(self) -> Result<ServiceHandle, Box<dyn std::error::Error>> {
+        let mut listeners = vec![];
+        for address in Self::addresses() {
+            for addr in lookup_host(&address).await? {
+                let listener = Self::bind(addr).await?;
+                debug!("Listening on {}", listener.local_addr()?);
+                listeners.push(listener);
+            }
+        }
+        Ok(self.spawn_many(listeners))
+    }
+
+    /// Bind listener with [`TcpServiceServer::reuse_address`],
+    /// retrying with backoff while the address is in use.
+    async fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+        let mut delay = Self::bind_backoff();
+        let mut attempt = 0;
+        loop {
+            let socket = match addr {
+                SocketAddr::V4(_) => TcpSocket::new_v4()?,
+                SocketAddr::V6(_) => TcpSocket::new_v6()?,
+            };
+            socket.set_reuseaddr(Self::reuse_address())?;
+            match socket.bind(addr).and_then(|_| socket.listen(1024)) {
+                Err(e) if e.kind() == ErrorKind::AddrInUse && attempt < Self::bind_retries() => {
+                    warn!("Address {addr} in use, retrying bind in {delay:?}");
+                    sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Run the service on already bound listener.
+    async fn serve(self, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve_many(vec![listener]).await
+    }
+
+    /// Run accept loop per listener, every connection go to the same handler.
+    /// Return when any of the listener fail.
+    async fn serve_many(
+        self,
+        listeners: Vec<TcpListener>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.spawn_many(listeners).handle.await??)
+    }
+
+    /// Same as [`TcpServiceServer::serve_many`] but run in background.
+    fn spawn_many(self, listeners: Vec<TcpListener>) -> ServiceHandle {
         let service = Arc::new(self);
-        let listener = TcpListener::bind(Self::address()).await?;
-        debug!("Listening on {}", Self::address());
+        let (state, shutdown) = ServiceState::new();
+        let mut loops = JoinSet::new();
+        for listener in listeners {
+            loops.spawn(Self::accept_loop(service.clone(), listener, state.clone()));
+        }
+        let handle = spawn(async move {
+            if loops.is_empty() {
+                return Err(std::io::Error::other("No listener to serve"));
+            }
+            while let Some(res) = loops.join_next().await {
+                res.map_err(std::io::Error::other)??;
+            }
+            Ok(())
+        });
+        ServiceHandle::new(&state, shutdown, handle)
+    }
+
+    /// Run the handler and log how long it took.
+    /// With the `tracing` feature the handler run inside span carrying request id and peer.
+    async fn timed_request(
+        service: Arc<Self>,
+        signal: Self::Signal,
+        peer: SocketAddr,
+    ) -> Result<Self::Response, Self::Error> {
+        let start = Instant::now();
+        #[cfg(feature = "tracing")]
+        let res = {
+            use tracing::Instrument;
+            let span = span::request_span(peer);
+            service.handle_request(signal).instrument(span).await
+        };
+        #[cfg(not(feature = "tracing"))]
+        let res = service.handle_request(signal).await;
+        let elapsed = start.elapsed();
+        if elapsed >= Self::slow_request_threshold() {
+            warn!("Slow request from {peer} took {elapsed:?}");
+        } else {
+            debug!("Request from {peer} took {elapsed:?}");
+        }
+        res
+    }
 
+    async fn accept_loop(
+        service: Arc<Self>,
+        listener: TcpListener,
+        mut state: ServiceState,
+    ) -> std::io::Result<()> {
         loop {
-            let (mut socket, _) = listener.accept().await?;
+            let (socket, peer) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = state.shutdown_requested() => {
+                    debug!("Stop listening on {:?}", listener.local_addr());
+                    return Ok(());
+                }
+            };
             let service_clone = Arc::clone(&service);
+            let guard = state.connection();
+            debug!("Accepted connection from {peer}");
 
             spawn(async move {
-                let mut buf = vec![];
-                match socket.read_to_end(&mut buf).await {
-                    Ok(_) => match bincode::deserialize::<Self::Signal>(&buf) {
-                        Ok(signal) => match service_clone.handle_request(signal).await {
-                            Ok(response) => match bincode::serialize(&response) {
-                                Ok(msg) => {
-                                    if let Err(e) = socket.write_all(&msg).await {
-                                        error!("Failed to write response: {}", e);
-                                    }
-                                }
-                                Err(e) => error!("Serialization error: {}", e),
-                            },
-                            Err(e) => error!("Request handling error: {}", e),
-                        },
-                        Err(e) => error!("Deserialization error: {}", e),
-                    },
-                    Err(e) => error!("Socket read error: {}", e),
-                }
+                let _guard = guard;
+                Self::handle_connection(service_clone, socket, peer).await;
             });
         }
     }
+
+    /// Read single request until EOF then write its response.
+    async fn handle_connection(service: Arc<Self>, mut socket: TcpStream, peer: SocketAddr) {
+        let mut buf = vec![];
+        if let Err(e) = socket.read_to_end(&mut buf).await {
+            error!("Socket read error: {}", e);
+            return;
+        }
+        if buf.is_empty() {
+            // empty response, the reply end at EOF
+            debug!("Ping from {peer}");
+            socket.shutdown().await.ok();
+            return;
+        }
+        let (key, frame) = match split_key(&buf) {
+            Ok(split) => split,
+            Err(e) => {
+                error!("Deserialization error: {}", e);
+                return;
+            }
+        };
+        let cache = service.idempotency().zip(key);
+        if let Some(msg) = cache.and_then(|(cache, key)| cache.get(key)) {
+            debug!("Answering {peer} with cached response of key {key:?}");
+            if let Err(e) = socket.write_all(&msg).await {
+                error!("Failed to write response: {}", e);
+            }
+            return;
+        }
+        if rejected::<Self>(frame) {
+            warn!("Rejected signal from {peer}");
+            return;
+        }
+
+        let (format, signal) = match Format::decode::<Self::Signal>(frame) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                error!("Deserialization error: {}", e);
+                return;
+            }
+        };
+        let response = match Self::timed_request(service.clone(), signal, peer).await {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Request handling error: {}", e);
+                return;
+            }
+        };
+        let msg = match format.encode(&response) {
+            Ok(msg) => msg,
+            Err(e) => {
+                error!("Serialization error: {}", e);
+                return;
+            }
+        };
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = service.metrics() {
+            metrics.record(buf.len(), msg.len());
+        }
+        if let Some((cache, key)) = cache {
+            cache.insert(key, msg.clone());
+        }
+        if let Err(e) = socket.write_all(&msg).await {
+            error!("Failed to write response: {}", e);
+        }
+    }
+}
+
+/// peeked variant is refused by [`TcpServiceServer::accept_variant`]
+fn rejected<S: TcpServiceServer>(buf: &[u8]) -> bool {
+    Format::peek_variant(buf).is_ok_and(|(_, variant)| !S::accept_variant(&variant))
 }
@@ -0,0 +1,14 @@
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Process wide counter so every request span get a unique id.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Span wrapping a single request, carrying its id and peer.
+/// Events emitted with `tracing` inside the handler are correlated by it.
+pub(crate) fn request_span(peer: SocketAddr) -> tracing::Span {
+    let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    tracing::info_span!("request", id, %peer)
+}
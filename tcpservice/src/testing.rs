@@ -0,0 +1,237 @@
+//! loopback server to test [`TcpServiceClient`](crate::TcpServiceClient) end to end
+//! enabled on test or with `testing` feature
+use std::{marker::PhantomData, net::SocketAddr, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::TcpListener;
+
+use crate::{ServiceHandle, TcpServiceServer};
+
+struct MockService<S, R, F> {
+    handler: F,
+    _marker: PhantomData<fn(S) -> R>,
+}
+
+#[async_trait::async_trait]
+impl<S, R, F> TcpServiceServer for MockService<S, R, F>
+where
+    S: Serialize + DeserializeOwned + Send + Sync + 'static,
+    R: Serialize + DeserializeOwned + Send + Sync + 'static,
+    F: Fn(S) -> R + Send + Sync + 'static,
+{
+    type Signal = S;
+    type Response = R;
+    type Error = std::io::Error;
+
+    fn address() -> String {
+        "127.0.0.1:0".to_string()
+    }
+
+    async fn handle_request(self: Arc<Self>, signal: S) -> Result<R, Self::Error> {
+        Ok((self.handler)(signal))
+    }
+}
+
+/// Server running the same framing as [`TcpServiceServer`] on ephemeral loopback port
+/// aborted when dropped
+pub struct MockServer {
+    pub address: SocketAddr,
+    pub service: ServiceHandle,
+}
+
+impl MockServer {
+    /// bind to ephemeral port and answer every request with the handler
+    pub async fn spawn<S, R, F>(handler: F) -> std::io::Result<Self>
+    where
+        S: Serialize + DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: Fn(S) -> R + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let service = MockService {
+            handler,
+            _marker: PhantomData,
+        };
+        let service = service.spawn_many(vec![listener]);
+        Ok(Self { address, service })
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.service.shutdown();
+        self.service.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TcpServiceClient;
+
+    struct Client;
+
+    impl TcpServiceClient for Client {
+        type Signal = String;
+        type Response = usize;
+
+        fn address() -> String {
+            unreachable!("test use send_request_to")
+        }
+    }
+
+    #[tokio::test]
+    async fn client_roundtrip() {
+        let server = MockServer::spawn(|s: String| s.len()).await.unwrap();
+        let res = Arc::new(Client)
+            .send_request_to(server.address, "hello".to_string())
+            .await
+            .unwrap();
+        assert_eq!(res, 5);
+    }
+
+    #[tokio::test]
+    async fn json_frame() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = MockServer::spawn(|s: String| s.len()).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(server.address)
+            .await
+            .unwrap();
+        stream.write_all(b"J\"hello\"").await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"J5");
+    }
+
+    #[tokio::test]
+    async fn headerless_bincode_frame() {
+        use crate::Format;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = MockServer::spawn(|s: String| s.len()).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(server.address)
+            .await
+            .unwrap();
+        let legacy = Format::Legacy.encode(&"hello".to_string()).unwrap();
+        stream.write_all(&legacy).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, 5usize.to_le_bytes());
+    }
+
+    #[tokio::test]
+    async fn ping() {
+        let server = MockServer::spawn(|s: String| s.len()).await.unwrap();
+        Client::ping_to(server.address).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_handle() {
+        let mut server = MockServer::spawn(|s: String| s.len()).await.unwrap();
+        assert_eq!(server.service.active_connections(), 0);
+        server.service.shutdown();
+        (&mut server.service.handle).await.unwrap().unwrap();
+        assert!(tokio::net::TcpStream::connect(server.address)
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "text")]
+    #[tokio::test]
+    async fn text_protocol() {
+        use crate::TextServer;
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let service = MockService {
+            handler: |s: String| s.len(),
+            _marker: PhantomData,
+        };
+        let _handle = service.spawn_text(listener);
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"hello\n\nhi\n").await.unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("5"));
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn peek_variant() {
+        use crate::{Format, Variant};
+
+        #[derive(Serialize)]
+        enum Signal {
+            Ping,
+            Upload(Vec<u8>),
+        }
+
+        let upload = Signal::Upload(vec![1; 1024]);
+        let peek = |format: Format, signal: &Signal| {
+            Format::peek_variant(&format.encode(signal).unwrap())
+                .unwrap()
+                .1
+        };
+        assert_eq!(peek(Format::Bincode, &Signal::Ping), Variant::Index(0));
+        assert_eq!(peek(Format::Bincode, &upload), Variant::Index(1));
+        assert_eq!(
+            peek(Format::Json, &Signal::Ping),
+            Variant::Name("Ping".into())
+        );
+        assert_eq!(peek(Format::Json, &upload), Variant::Name("Upload".into()));
+        assert!(Format::peek_variant(b"J 42").is_err());
+    }
+
+    #[tokio::test]
+    async fn idempotent_request_handled_once() {
+        use crate::IdempotencyCache;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counter {
+            calls: AtomicUsize,
+            cache: IdempotencyCache,
+        }
+
+        #[async_trait::async_trait]
+        impl TcpServiceServer for Counter {
+            type Signal = String;
+            type Response = usize;
+            type Error = std::io::Error;
+
+            fn address() -> String {
+                unreachable!("test bind its own listener")
+            }
+
+            fn idempotency(&self) -> Option<&IdempotencyCache> {
+                Some(&self.cache)
+            }
+
+            async fn handle_request(self: Arc<Self>, _: String) -> Result<usize, Self::Error> {
+                Ok(self.calls.fetch_add(1, Ordering::SeqCst) + 1)
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let _service = Counter::default().spawn_many(vec![listener]);
+
+        let client = Arc::new(Client);
+        let send = |key| {
+            client
+                .clone()
+                .send_idempotent_to(address, key, "restart".to_string())
+        };
+        assert_eq!(send("a").await.unwrap(), 1);
+        assert_eq!(send("a").await.unwrap(), 1);
+        assert_eq!(send("b").await.unwrap(), 2);
+        let res = client
+            .clone()
+            .send_request_to(address, "restart".to_string());
+        assert_eq!(res.await.unwrap(), 3);
+    }
+}
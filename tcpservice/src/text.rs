@@ -0,0 +1,86 @@
+use std::{fmt::Display, net::SocketAddr, str::FromStr, sync::Arc};
+
+use log::debug;
+use servicecore::text;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    spawn,
+};
+
+use crate::{ServiceHandle, ServiceState, TcpServiceServer};
+
+/// Newline delimited text protocol to poke the service by hand with `nc`/`socat`, debugging only.
+/// Every line is parsed into the signal with `FromStr` and answered with the `Display` of the
+/// response on its own line, failure is answered with `error: ...`.
+/// Available on every server whose signal and response implement them, serve it on separate port.
+pub trait TextServer: TcpServiceServer {
+    /// Run the text protocol on the listener in background.
+    fn spawn_text(self, listener: TcpListener) -> ServiceHandle;
+}
+
+impl<S> TextServer for S
+where
+    S: TcpServiceServer,
+    S::Signal: FromStr,
+    <S::Signal as FromStr>::Err: Display,
+    S::Response: Display,
+{
+    fn spawn_text(self, listener: TcpListener) -> ServiceHandle {
+        let service = Arc::new(self);
+        let (state, shutdown) = ServiceState::new();
+        let handle = spawn(accept_loop(service, listener, state.clone()));
+        ServiceHandle::new(&state, shutdown, handle)
+    }
+}
+
+async fn accept_loop<S>(
+    service: Arc<S>,
+    listener: TcpListener,
+    mut state: ServiceState,
+) -> std::io::Result<()>
+where
+    S: TcpServiceServer,
+    S::Signal: FromStr,
+    <S::Signal as FromStr>::Err: Display,
+    S::Response: Display,
+{
+    loop {
+        let (socket, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = state.shutdown_requested() => return Ok(()),
+        };
+        let guard = state.connection();
+        debug!("Accepted text connection from {peer}");
+        let service = service.clone();
+        spawn(async move {
+            let _guard = guard;
+            handle_lines(service, socket, peer).await;
+        });
+    }
+}
+
+/// answer line by line until the peer close the connection
+async fn handle_lines<S>(service: Arc<S>, socket: TcpStream, peer: SocketAddr)
+where
+    S: TcpServiceServer,
+    S::Signal: FromStr,
+    <S::Signal as FromStr>::Err: Display,
+    S::Response: Display,
+{
+    let (read, mut write) = socket.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = match text::parse_line::<S::Signal>(&line) {
+            None => continue,
+            Some(Ok(signal)) => {
+                text::encode_reply(S::timed_request(service.clone(), signal, peer).await)
+            }
+            Some(Err(reply)) => reply,
+        };
+        if write.write_all(reply.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+    debug!("Text connection from {peer} closed");
+}
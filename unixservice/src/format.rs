@@ -0,0 +1,37 @@
+use std::io::{self, Read, Write};
+
+/// biggest frame accepted, protect against garbage length allocating the world
+pub const MAX_FRAME_LEN: u32 = 64 * 1024 * 1024;
+
+/// write the message prefixed with its length as big endian u32
+/// so the other side know where it end without closing the connection
+/// ```text
+/// [len: u32 BE][header: u8][payload...]
+/// ```
+/// `len` count the header and payload, see [`Format`](crate::Format) for the header
+/// e.g. `printf '\x00\x00\x00\x07J"Ping"' | nc -U /tmp/app.sock`
+pub fn write_frame(w: &mut impl Write, msg: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(msg.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(msg)?;
+    w.flush()
+}
+
+/// read single length prefixed message written by [`write_frame`]
+pub fn read_frame(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    r.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame length {len} exceed limit"),
+        ));
+    }
+    let mut buf = vec![0; len as usize];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
@@ -0,0 +1,61 @@
+use std::{
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+/// shared between the accept loop and the [`ServiceHandle`]
+#[derive(Debug, Clone, Default)]
+pub struct ServiceState {
+    active: Arc<AtomicUsize>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl ServiceState {
+    pub(crate) fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// count the connection as active until the guard is dropped
+    pub(crate) fn connection(&self) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(self.active.clone())
+    }
+}
+
+pub(crate) struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// control of service spawned by [`UnixServiceServer::start_service`](crate::UnixServiceServer::start_service)
+#[derive(Debug)]
+pub struct ServiceHandle {
+    pub(crate) state: ServiceState,
+    pub(crate) socket_path: Option<PathBuf>,
+    /// finish once the accept loop stopped
+    pub handle: JoinHandle<()>,
+}
+
+impl ServiceHandle {
+    /// stop accepting new connection, in flight request still finish
+    pub fn shutdown(&self) {
+        self.state.shutdown.store(true, Ordering::SeqCst);
+        // accept is blocking, wake it up so the loop see the flag
+        if let Some(path) = &self.socket_path {
+            UnixStream::connect(path).ok();
+        }
+    }
+
+    /// connection currently being served
+    pub fn active_connections(&self) -> usize {
+        self.state.active.load(Ordering::SeqCst)
+    }
+}
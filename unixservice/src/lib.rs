@@ -1,13 +1,32 @@
-use bincode::{deserialize, serialize};
+use appname::exe_name;
 use log::{debug, error, warn};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    io::{Read, Write},
-    os::unix::net::UnixStream,
+    os::unix::net::{UnixListener, UnixStream},
     path::Path,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+mod format;
+mod handle;
+mod peer;
+#[cfg(feature = "tracing")]
+mod span;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "text")]
+mod text;
+
+pub use format::{read_frame, write_frame, MAX_FRAME_LEN};
+pub use handle::{ServiceHandle, ServiceState};
+use peer::{peer_pid, PeerFailures};
+pub use servicecore::{Format, FrameError, Variant};
+#[cfg(feature = "metrics")]
+pub use servicecore::{MessageMetrics, MetricsSnapshot};
+#[cfg(feature = "text")]
+pub use text::TextServer;
+
 pub trait UnixServiceClient: Sized + Send + Sync + 'static {
     /// this type better serve as signal (enum)
     type Signal: Serialize + DeserializeOwned + Send + Sync + 'static;
@@ -16,6 +35,26 @@ pub trait UnixServiceClient: Sized + Send + Sync + 'static {
 
     /// required to connect to socket name
     fn name() -> String;
+    /// encoding used to talk with the server, json is handy for debugging
+    fn format() -> Format {
+        Format::Bincode
+    }
+    /// check the server is up without sending real signal
+    /// send empty request which the server answer with empty response, same as tcpservice
+    fn ping() -> Result<(), Box<dyn std::error::Error>> {
+        Self::ping_to(Path::new("/tmp").join(format!("{}.sock", Self::name())))
+    }
+
+    /// same as [`UnixServiceClient::ping`] on the given socket path
+    fn ping_to(socket_path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut stream = UnixStream::connect(socket_path.as_ref())?;
+        write_frame(&mut stream, &[])?;
+        if !read_frame(&mut stream)?.is_empty() {
+            return Err("Unexpected ping response".into());
+        }
+        Ok(())
+    }
+
     /// the self is reference counter so feel to use it
     fn handle_response(
         self: Arc<Self>,
@@ -27,22 +66,32 @@ pub trait UnixServiceClient: Sized + Send + Sync + 'static {
         signal: Self::Signal,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let socket_path = Path::new("/tmp").join(format!("{}.sock", Self::name()));
+        self.send_request_to(socket_path, signal)
+    }
 
-        match UnixStream::connect(&socket_path) {
+    /// send signal into the given socket path instead of the one from [`UnixServiceClient::name`]
+    fn send_request_to(
+        self: Arc<Self>,
+        socket_path: impl AsRef<Path>,
+        signal: Self::Signal,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match UnixStream::connect(socket_path.as_ref()) {
             Ok(mut stream) => {
-                let msg = serialize(&signal)?;
-                if let Err(e) = stream.write_all(&msg) {
+                let msg = Self::format().encode(&signal)?;
+                if let Err(e) = write_frame(&mut stream, &msg) {
                     error!("Error writing to stream: {}", e);
                     return Err(Box::new(e));
                 }
 
-                let mut buf = vec![];
-                if let Err(e) = stream.read_to_end(&mut buf) {
-                    error!("Error reading from stream: {}", e);
-                    return Err(Box::new(e));
-                }
+                let buf = match read_frame(&mut stream) {
+                    Ok(buf) => buf,
+                    Err(e) => {
+                        error!("Error reading from stream: {}", e);
+                        return Err(Box::new(e));
+                    }
+                };
 
-                self.handle_response(deserialize(&buf)?)
+                self.handle_response(Format::decode(&buf)?.1)
             }
             Err(e) => {
                 error!("Error connecting to socket: {}", e);
@@ -58,16 +107,59 @@ pub trait UnixServiceServer: Sized + Sync + Send + 'static {
     /// this type better serve as signal (enum)
     type Response: Serialize + DeserializeOwned + Send + Sync + 'static;
 
+    /// socket is `/tmp/{name}.sock`, default to the running executable name
+    /// e.g. `/tmp/myapp.sock` for `target/release/myapp`, this crate name when it cant be resolved
     fn name() -> String {
-        env!("CARGO_PKG_NAME").to_string()
+        exe_name(env!("CARGO_PKG_NAME"))
+    }
+    /// cheap check on the signal enum variant before the full deserialize
+    /// e.g. reject variant the server doesnt handle, default to accept everything
+    fn accept_variant(_variant: &Variant) -> bool {
+        true
+    }
+
+    /// drop peer after this many consecutive malformed request, 0 disable it
+    /// malformed is garbage frame, rejected variant or signal failing to deserialize
+    /// peer is identified by its pid (`SO_PEERCRED`), not tracked where its unavailable
+    fn max_decode_failures() -> u32 {
+        5
+    }
+    /// refuse connection of the dropped peer for this long, default to a minute
+    /// None only reset its count, every connection is single request so nothing is refused
+    fn decode_failure_ban() -> Option<Duration> {
+        Some(Duration::from_secs(60))
+    }
+
+    /// how long reading the request frame may stall before the connection is dropped
+    /// None wait forever, only the connection thread is held by it
+    fn read_timeout() -> Option<Duration> {
+        Some(Duration::from_secs(10))
+    }
+
+    /// size accounting of every request/response, keep it in the service struct
+    #[cfg(feature = "metrics")]
+    fn metrics(&self) -> Option<&MessageMetrics> {
+        None
     }
     /// the self is reference counter so feel to use it
+    /// run inside the request span with `tracing` feature, use `tracing` macros to correlate
     fn handle_request(
         self: Arc<Self>,
         signal: Self::Signal,
     ) -> Result<Self::Response, Box<dyn std::error::Error>>;
 
     fn create_service(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve(Self::bind()?)
+    }
+
+    /// bind then run the service in background thread
+    /// return handle to shut it down and inspect it
+    fn start_service(self) -> Result<ServiceHandle, Box<dyn std::error::Error>> {
+        Ok(self.spawn_service(Self::bind()?))
+    }
+
+    /// bind socket from [`UnixServiceServer::name`], removing the old one
+    fn bind() -> Result<UnixListener, Box<dyn std::error::Error>> {
         let socket_path = Path::new("/tmp").join(format!("{}.sock", Self::name()));
 
         if socket_path.exists() {
@@ -78,42 +170,68 @@ pub trait UnixServiceServer: Sized + Sync + Send + 'static {
             }
         }
 
-        let m = Arc::new(self);
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path)?;
+        let listener = UnixListener::bind(&socket_path)?;
         debug!("Listening on {:?}", socket_path);
+        Ok(listener)
+    }
+
+    /// run the service on already bound listener
+    fn serve(self, listener: UnixListener) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve_with(listener, ServiceState::default())
+    }
+
+    /// same as [`UnixServiceServer::serve`] but run in background thread
+    fn spawn_service(self, listener: UnixListener) -> ServiceHandle {
+        let state = ServiceState::default();
+        let socket_path = listener
+            .local_addr()
+            .ok()
+            .and_then(|a| a.as_pathname().map(Path::to_path_buf));
+        let loop_state = state.clone();
+        let handle = std::thread::spawn(move || {
+            if let Err(e) = self.serve_with(listener, loop_state) {
+                error!("Service stopped: {e}");
+            }
+        });
+        ServiceHandle {
+            state,
+            socket_path,
+            handle,
+        }
+    }
+
+    /// accept loop, stop once shutdown is requested on the state
+    fn serve_with(
+        self,
+        listener: UnixListener,
+        state: ServiceState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let m = Arc::new(self);
+        let failures = Arc::new(Mutex::new(PeerFailures::new(
+            Self::max_decode_failures(),
+            Self::decode_failure_ban(),
+        )));
 
         for request in listener.incoming() {
+            if state.is_shutdown() {
+                debug!("Service shutdown requested");
+                break;
+            }
             match request {
-                Ok(mut stream) => {
-                    debug!("Received connection from {:?}", stream.peer_addr());
-                    let mut buffer = vec![];
-                    match stream.read_to_end(&mut buffer) {
-                        Ok(_) => match deserialize::<Self::Signal>(&buffer) {
-                            Ok(signal) => {
-                                let mc = m.clone();
-                                std::thread::spawn(move || {
-                                    match Self::handle_request(mc, signal) {
-                                        Ok(response) => {
-                                            if let Ok(r) = serialize(&response) {
-                                                if let Err(e) = stream.write_all(&r) {
-                                                    error!("Failed to send response: {}", e);
-                                                }
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!("Error handling request: {}", e);
-                                        }
-                                    }
-                                });
-                            }
-                            Err(e) => {
-                                error!("Failed to deserialize signal: {}", e);
-                            }
-                        },
-                        Err(e) => {
-                            error!("Error reading data from stream: {:?}", e);
-                        }
+                Ok(stream) => {
+                    let peer = peer_pid(&stream);
+                    if let Some(pid) = peer.filter(|pid| failures.lock().unwrap().is_banned(*pid)) {
+                        debug!("Refused connection from banned peer {pid}");
+                        continue;
                     }
+                    let guard = state.connection();
+                    debug!("Received connection from {:?}", stream.peer_addr());
+                    let (mc, failures) = (m.clone(), failures.clone());
+                    // read on its own thread so stalled client doesnt block the accept loop
+                    std::thread::spawn(move || {
+                        let _guard = guard;
+                        handle_connection(mc, stream, peer, &failures);
+                    });
                 }
                 Err(e) => {
                     warn!("Error accepting connection: {}", e);
@@ -124,3 +242,89 @@ pub trait UnixServiceServer: Sized + Sync + Send + 'static {
         Ok(())
     }
 }
+
+/// read the single request of the connection then answer it
+fn handle_connection<S: UnixServiceServer>(
+    service: Arc<S>,
+    mut stream: UnixStream,
+    peer: Option<u32>,
+    failures: &Mutex<PeerFailures>,
+) {
+    if let Err(e) = stream.set_read_timeout(S::read_timeout()) {
+        error!("Failed to set read timeout: {e}");
+        return;
+    }
+    let buffer = match read_frame(&mut stream) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            error!("Error reading data from stream: {:?}", e);
+            if garbage(&e) {
+                malformed::<S>(failures, peer);
+            }
+            return;
+        }
+    };
+    if buffer.is_empty() {
+        debug!("Ping from {:?}", stream.peer_addr());
+        write_frame(&mut stream, &[]).ok();
+        return;
+    }
+    if rejected::<S>(&buffer) {
+        warn!("Rejected signal from {:?}", stream.peer_addr());
+        malformed::<S>(failures, peer);
+        return;
+    }
+    let (format, signal) = match Format::decode::<S::Signal>(&buffer) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            error!("Failed to deserialize signal: {}", e);
+            malformed::<S>(failures, peer);
+            return;
+        }
+    };
+    if let Some(pid) = peer {
+        failures.lock().unwrap().success(pid);
+    }
+    #[cfg(feature = "tracing")]
+    let _span = span::request_span(peer).entered();
+    match S::handle_request(service.clone(), signal) {
+        Ok(response) => {
+            if let Ok(r) = format.encode(&response) {
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = service.metrics() {
+                    metrics.record(buffer.len(), r.len());
+                }
+                if let Err(e) = write_frame(&mut stream, &r) {
+                    error!("Failed to send response: {}", e);
+                }
+            }
+        }
+        Err(e) => {
+            error!("Error handling request: {}", e);
+        }
+    }
+}
+
+/// frame that cant be read because of its content rather than the connection,
+/// e.g. bogus length over [`MAX_FRAME_LEN`] or shorter frame than its length
+fn garbage(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof
+    )
+}
+
+/// count malformed request of the peer, logging once its dropped
+fn malformed<S: UnixServiceServer>(failures: &Mutex<PeerFailures>, peer: Option<u32>) {
+    if let Some(pid) = peer.filter(|pid| failures.lock().unwrap().failure(*pid)) {
+        warn!(
+            "Dropping peer {pid} after {} consecutive malformed request",
+            S::max_decode_failures()
+        );
+    }
+}
+
+/// peeked variant is refused by [`UnixServiceServer::accept_variant`]
+fn rejected<S: UnixServiceServer>(buf: &[u8]) -> bool {
+    Format::peek_variant(buf).is_ok_and(|(_, variant)| !S::accept_variant(&variant))
+}
@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    os::unix::net::UnixStream,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Default)]
+struct Failures {
+    count: u32,
+    banned_until: Option<Instant>,
+}
+
+/// consecutive malformed request of each peer, shared by the connection threads
+#[derive(Debug)]
+pub(crate) struct PeerFailures {
+    peers: HashMap<u32, Failures>,
+    max: u32,
+    ban: Option<Duration>,
+}
+
+impl PeerFailures {
+    pub(crate) fn new(max: u32, ban: Option<Duration>) -> Self {
+        Self {
+            peers: HashMap::new(),
+            max,
+            ban,
+        }
+    }
+
+    pub(crate) fn is_banned(&mut self, peer: u32) -> bool {
+        let now = Instant::now();
+        self.peers
+            .retain(|_, f| f.count > 0 || f.banned_until.is_some_and(|until| until > now));
+        self.peers
+            .get(&peer)
+            .is_some_and(|f| f.banned_until.is_some_and(|until| until > now))
+    }
+
+    pub(crate) fn success(&mut self, peer: u32) {
+        self.peers.remove(&peer);
+    }
+
+    /// true once the peer reach the threshold, its count start again after that
+    pub(crate) fn failure(&mut self, peer: u32) -> bool {
+        if self.max == 0 {
+            return false;
+        }
+        let failures = self.peers.entry(peer).or_default();
+        failures.count += 1;
+        if failures.count < self.max {
+            return false;
+        }
+        failures.count = 0;
+        failures.banned_until = self.ban.map(|ban| Instant::now() + ban);
+        true
+    }
+}
+
+/// pid of the connected process from `SO_PEERCRED`, None where its not supported
+pub(crate) fn peer_pid(stream: &UnixStream) -> Option<u32> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let res = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        (res == 0).then_some(cred.pid as u32)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_then_ban_after_threshold() {
+        let mut failures = PeerFailures::new(2, Some(Duration::from_secs(60)));
+        assert!(!failures.failure(1));
+        failures.success(1);
+        assert!(!failures.failure(1));
+        assert!(failures.failure(1));
+        assert!(failures.is_banned(1));
+        assert!(!failures.is_banned(2));
+
+        let mut no_ban = PeerFailures::new(1, None);
+        assert!(no_ban.failure(1));
+        assert!(!no_ban.is_banned(1));
+    }
+}
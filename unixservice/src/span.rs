@@ -0,0 +1,11 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// process wide counter so every request span get unique id
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// span wrapping single request with its id and peer pid when its known
+/// event emitted with `tracing` inside the handler is correlated by it
+pub(crate) fn request_span(peer: Option<u32>) -> tracing::Span {
+    let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    tracing::info_span!("request", id, peer)
+}
@@ -0,0 +1,192 @@
+//! socket server to test [`UnixServiceClient`](crate::UnixServiceClient) end to end
+//! enabled on test or with `testing` feature
+use std::{
+    marker::PhantomData,
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ServiceHandle, UnixServiceServer};
+
+struct MockService<S, R, F> {
+    handler: F,
+    _marker: PhantomData<fn(S) -> R>,
+}
+
+impl<S, R, F> UnixServiceServer for MockService<S, R, F>
+where
+    S: Serialize + DeserializeOwned + Send + Sync + 'static,
+    R: Serialize + DeserializeOwned + Send + Sync + 'static,
+    F: Fn(S) -> R + Send + Sync + 'static,
+{
+    type Signal = S;
+    type Response = R;
+
+    fn handle_request(self: Arc<Self>, signal: S) -> Result<R, Box<dyn std::error::Error>> {
+        Ok((self.handler)(signal))
+    }
+}
+
+/// Server running the same framing as [`UnixServiceServer`] on a socket in temp dir
+/// dropping it stop the accept thread, wait for it then remove the socket file
+pub struct MockServer {
+    pub socket_path: PathBuf,
+    pub service: ServiceHandle,
+}
+
+impl MockServer {
+    /// bind unique socket in temp dir and answer every request with the handler
+    pub fn spawn<S, R, F>(handler: F) -> std::io::Result<Self>
+    where
+        S: Serialize + DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: Fn(S) -> R + Send + Sync + 'static,
+    {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let socket_path = std::env::temp_dir().join(format!(
+            "unixservice-mock-{}-{}.sock",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::remove_file(&socket_path).ok();
+
+        let listener = UnixListener::bind(&socket_path)?;
+        let service = MockService {
+            handler,
+            _marker: PhantomData,
+        };
+        let service = service.spawn_service(listener);
+        Ok(Self {
+            socket_path,
+            service,
+        })
+    }
+}
+
+/// how long dropping [`MockServer`] wait for its accept thread
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // wake up the blocking accept then join it, the handle cant be moved out of the field
+        self.service.shutdown();
+        let deadline = Instant::now() + JOIN_TIMEOUT;
+        while !self.service.handle.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        std::fs::remove_file(&self.socket_path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::UnixServiceClient;
+
+    #[derive(Default)]
+    struct Client(Mutex<Option<usize>>);
+
+    impl UnixServiceClient for Client {
+        type Signal = String;
+        type Response = usize;
+
+        fn name() -> String {
+            unreachable!("test use send_request_to")
+        }
+
+        fn handle_response(
+            self: Arc<Self>,
+            res: Self::Response,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            *self.0.lock().unwrap() = Some(res);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn client_roundtrip() {
+        let server = MockServer::spawn(|s: String| s.len()).unwrap();
+        let client = Arc::new(Client::default());
+        client
+            .clone()
+            .send_request_to(&server.socket_path, "hello".to_string())
+            .unwrap();
+        assert_eq!(*client.0.lock().unwrap(), Some(5));
+    }
+
+    #[test]
+    fn ping() {
+        let server = MockServer::spawn(|s: String| s.len()).unwrap();
+        Client::ping_to(&server.socket_path).unwrap();
+        let socket_path = server.socket_path.clone();
+        drop(server);
+        assert!(!socket_path.exists());
+        assert!(Client::ping_to(std::env::temp_dir().join("missing.sock")).is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn garbage_frame_ban_peer() {
+        use std::io::{Read, Write};
+
+        let server = MockServer::spawn(|s: String| s.len()).unwrap();
+        for _ in 0..5 {
+            let mut stream = std::os::unix::net::UnixStream::connect(&server.socket_path).unwrap();
+            stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+            // closed without answer once the server gave up on it
+            stream.read_to_end(&mut vec![]).unwrap();
+        }
+        assert!(Client::ping_to(&server.socket_path).is_err());
+    }
+
+    #[test]
+    fn stalled_client_dont_block_others() {
+        let server = MockServer::spawn(|s: String| s.len()).unwrap();
+        // connected without sending anything
+        let _stalled = std::os::unix::net::UnixStream::connect(&server.socket_path).unwrap();
+        Client::ping_to(&server.socket_path).unwrap();
+    }
+
+    #[cfg(feature = "text")]
+    #[test]
+    fn text_protocol() {
+        use crate::TextServer;
+        use std::io::{BufRead, BufReader, Write};
+
+        let socket_path =
+            std::env::temp_dir().join(format!("unixservice-text-{}.sock", std::process::id()));
+        std::fs::remove_file(&socket_path).ok();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let service = MockService {
+            handler: |s: String| s.len(),
+            _marker: PhantomData,
+        };
+        let handle = service.spawn_text(listener);
+        let mut stream = std::os::unix::net::UnixStream::connect(&socket_path).unwrap();
+        stream.write_all(b"hello\n\nhi\n").unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "5");
+        assert_eq!(lines.next().unwrap().unwrap(), "2");
+        handle.shutdown();
+        std::fs::remove_file(&socket_path).ok();
+    }
+
+    #[test]
+    fn shutdown_handle() {
+        let server = MockServer::spawn(|s: String| s.len()).unwrap();
+        assert_eq!(server.service.active_connections(), 0);
+        server.service.shutdown();
+        while !server.service.handle.is_finished() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fmt::Display,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+use log::{debug, warn};
+use servicecore::text;
+
+use crate::{ServiceHandle, ServiceState, UnixServiceServer};
+
+/// newline delimited text protocol to poke the service by hand with `socat`, debugging only
+/// every line is parsed into the signal with `FromStr` and answered with the `Display` of the
+/// response on its own line, failure is answered with `error: ...`
+/// available on every server whose signal and response implement them, serve it on separate socket
+pub trait TextServer: UnixServiceServer {
+    /// run the text protocol on the listener in background thread
+    fn spawn_text(self, listener: UnixListener) -> ServiceHandle;
+}
+
+impl<S> TextServer for S
+where
+    S: UnixServiceServer,
+    S::Signal: FromStr,
+    <S::Signal as FromStr>::Err: Display,
+    S::Response: Display,
+{
+    fn spawn_text(self, listener: UnixListener) -> ServiceHandle {
+        let state = ServiceState::default();
+        let socket_path = listener
+            .local_addr()
+            .ok()
+            .and_then(|a| a.as_pathname().map(Path::to_path_buf));
+        let service = Arc::new(self);
+        let loop_state = state.clone();
+        let handle = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if loop_state.is_shutdown() {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let guard = loop_state.connection();
+                        let service = service.clone();
+                        std::thread::spawn(move || {
+                            let _guard = guard;
+                            handle_lines(service, stream);
+                        });
+                    }
+                    Err(e) => warn!("Error accepting text connection: {e}"),
+                }
+            }
+        });
+        ServiceHandle {
+            state,
+            socket_path,
+            handle,
+        }
+    }
+}
+
+/// answer line by line until the peer close the connection
+fn handle_lines<S>(service: Arc<S>, stream: UnixStream)
+where
+    S: UnixServiceServer,
+    S::Signal: FromStr,
+    <S::Signal as FromStr>::Err: Display,
+    S::Response: Display,
+{
+    let Ok(mut write) = stream.try_clone() else {
+        return;
+    };
+    #[cfg(feature = "tracing")]
+    let peer = crate::peer::peer_pid(&stream);
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Some(parsed) = text::parse_line::<S::Signal>(&line) else {
+            continue;
+        };
+        #[cfg(feature = "tracing")]
+        let _span = crate::span::request_span(peer).entered();
+        let reply = match parsed {
+            Ok(signal) => text::encode_reply(service.clone().handle_request(signal)),
+            Err(reply) => reply,
+        };
+        if write.write_all(reply.as_bytes()).is_err() {
+            break;
+        }
+    }
+    debug!("Text connection closed");
+}